@@ -1,12 +1,107 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
-pub use self::button::ButtonRef;
+pub use self::button::{
+    ButtonRef, FlashMintConfig, TransferFeeConfig, METADATA_ADMIN, MINTER, PAUSER,
+};
 
-#[openbrush::implementation(PSP22, PSP22Metadata, PSP22Capped)]
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+};
+use openbrush::contracts::psp22::PSP22Error;
+
+type Balance = u128;
+type Timestamp = u64;
+
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum FlashBorrowerError {
+    FlashloanRejected(String),
+}
+
+// Receiver of a flash mint. It has `amount` minted to it and must approve the
+// token to take back `amount + fee` before returning.
+#[ink::trait_definition]
+pub trait FlashBorrower {
+    #[ink(message)]
+    fn on_flashloan(
+        &mut self,
+        initiator: AccountId,
+        token: AccountId,
+        amount: Balance,
+        fee: Balance,
+        data: Vec<u8>,
+    ) -> Result<(), FlashBorrowerError>;
+}
+
+#[ink::trait_definition]
+pub trait FlashLender {
+    #[ink(message)]
+    fn max_flashloan(&self, token: AccountId) -> Balance;
+
+    #[ink(message)]
+    fn flash_fee(&self, token: AccountId, amount: Balance) -> Result<Balance, PSP22Error>;
+
+    #[ink(message)]
+    fn flashloan(
+        &mut self,
+        receiver: AccountId,
+        token: AccountId,
+        amount: Balance,
+        data: Vec<u8>,
+    ) -> Result<(), PSP22Error>;
+}
+
+// Approvals signed off-chain by the owner's ECDSA key, so that a spender can
+// be approved and use the allowance in a single transaction.
+#[ink::trait_definition]
+pub trait Permit {
+    #[ink(message)]
+    fn permit_nonce(&self, owner: AccountId) -> u64;
+
+    // signature is over blake2x256(scale_encode((token, owner, spender, value, nonce, deadline)))
+    #[ink(message)]
+    fn permit(
+        &mut self,
+        owner: AccountId,
+        spender: AccountId,
+        value: Balance,
+        deadline: Timestamp,
+        signature: [u8; 65],
+    ) -> Result<(), PSP22Error>;
+}
+
+#[openbrush::implementation(
+    PSP22,
+    PSP22Metadata,
+    PSP22Capped,
+    PSP22Mintable,
+    AccessControl,
+    Ownable,
+    Pausable
+)]
 #[openbrush::contract]
 pub mod button {
-    use ink::codegen::{EmitEvent, Env};
-    use openbrush::traits::Storage;
+    use super::{FlashBorrowerError, FlashLender, Permit};
+    use ink::{
+        codegen::{EmitEvent, Env},
+        env::{
+            call::{build_call, ExecutionInput, Selector},
+            hash::Blake2x256,
+            CallFlags, DefaultEnvironment,
+        },
+        prelude::vec::Vec,
+        storage::{Lazy, Mapping},
+    };
+    use openbrush::{modifiers, traits::Storage};
+
+    // === CONSTANTS ===
+    // Each transfer writes two balances plus any snapshot and vote checkpoints,
+    // so this keeps a full batch well inside the block weight limit.
+    pub const MAX_BATCH_TRANSFER_SIZE: usize = 100;
+    pub const METADATA_ADMIN: RoleType = ink::selector_id!("METADATA_ADMIN");
+    pub const MINTER: RoleType = ink::selector_id!("MINTER");
+    pub const PAUSER: RoleType = ink::selector_id!("PAUSER");
 
     // === EVENTS ===
     /// Event emitted when a token transfer occurs.
@@ -30,7 +125,62 @@ pub mod button {
         value: Balance,
     }
 
+    /// Event emitted when `burner` burns `amount` tokens from `account`.
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        burner: AccountId,
+        amount: Balance,
+    }
+
+    /// Event emitted when an account changes its delegate.
+    #[ink(event)]
+    pub struct DelegateChanged {
+        #[ink(topic)]
+        delegator: AccountId,
+        from_delegate: Option<AccountId>,
+        to_delegate: Option<AccountId>,
+    }
+
+    /// Event emitted when a delegate's voting power changes.
+    #[ink(event)]
+    pub struct DelegateVotesChanged {
+        #[ink(topic)]
+        delegate: AccountId,
+        previous_votes: Balance,
+        new_votes: Balance,
+    }
+
+    /// Event emitted when a balance snapshot is taken.
+    #[ink(event)]
+    pub struct Snapshot {
+        id: u32,
+    }
+
     // === STRUCTS ===
+    // A limit of zero disables flash minting.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct FlashMintConfig {
+        pub limit: Balance,
+        pub fee_basis_points: u16,
+    }
+
+    #[derive(scale::Decode, scale::Encode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TransferFeeConfig {
+        pub treasury: AccountId,
+        pub fee_basis_points: u16,
+    }
+
     #[ink(storage)]
     #[derive(Default, Storage)]
     pub struct Button {
@@ -40,6 +190,79 @@ pub mod button {
         metadata: metadata::Data,
         #[storage_field]
         cap: capped::Data,
+        #[storage_field]
+        access: access_control::Data,
+        #[storage_field]
+        ownable: ownable::Data,
+        #[storage_field]
+        pause: pausable::Data,
+        pause_exempt: Mapping<AccountId, ()>,
+        pending_owner: Lazy<Option<AccountId>>,
+        permit_nonces: Mapping<AccountId, u64>,
+        // (snapshot id, value before it changed after that snapshot), keyed by
+        // position so that lookups read O(log n) entries and writes one
+        account_balance_snapshot_counts: Mapping<AccountId, u32>,
+        account_balance_snapshots: Mapping<(AccountId, u32), (u32, Balance)>,
+        snapshot_id: Lazy<u32>,
+        total_supply_snapshot_count: Lazy<u32>,
+        total_supply_snapshots: Mapping<u32, (u32, Balance)>,
+        delegates: Mapping<AccountId, AccountId>,
+        // (block number, votes from that block on), keyed by position
+        vote_checkpoint_counts: Mapping<AccountId, u32>,
+        vote_checkpoints: Mapping<(AccountId, u32), (BlockNumber, Balance)>,
+        total_burned: Lazy<Balance>,
+        flash_mint_config: Lazy<FlashMintConfig>,
+        transfer_fee_config: Lazy<Option<TransferFeeConfig>>,
+        transfer_fee_exempt: Mapping<AccountId, ()>,
+    }
+
+    #[overrider(psp22::Internal)]
+    fn _before_token_transfer(
+        &mut self,
+        from: Option<&AccountId>,
+        to: Option<&AccountId>,
+        amount: &Balance,
+    ) -> Result<(), PSP22Error> {
+        // `is_none` means that it is minting
+        if from.is_none() && capped::Internal::_is_cap_exceeded(self, amount) {
+            return Err(PSP22Error::Custom(String::from("Cap exceeded")));
+        }
+        // While paused, tokens only move when the caller, sender or recipient
+        // is exempt e.g. a sale contract transferring or minting to buyers.
+        if pausable::Internal::_paused(self) {
+            let caller: AccountId = Self::env().caller();
+            let exempt: bool = [Some(&caller), from, to]
+                .into_iter()
+                .flatten()
+                .any(|account| self.pause_exempt.contains(account));
+            if !exempt {
+                return Err(PSP22Error::from(PausableError::Paused));
+            }
+        }
+
+        // Record values as they were at the latest snapshot, before they change
+        for account in [from, to].into_iter().flatten() {
+            self.update_account_balance_snapshot(*account);
+        }
+        if from.is_none() || to.is_none() {
+            self.update_total_supply_snapshot();
+        }
+
+        Ok(())
+    }
+
+    #[overrider(psp22::Internal)]
+    fn _after_token_transfer(
+        &mut self,
+        from: Option<&AccountId>,
+        to: Option<&AccountId>,
+        amount: &Balance,
+    ) -> Result<(), PSP22Error> {
+        let from_delegate: Option<AccountId> = from.and_then(|account| self.delegates(*account));
+        let to_delegate: Option<AccountId> = to.and_then(|account| self.delegates(*account));
+        self.move_voting_power(from_delegate, to_delegate, *amount);
+
+        Ok(())
     }
 
     #[overrider(psp22::Internal)]
@@ -65,6 +288,185 @@ pub mod button {
         });
     }
 
+    #[overrider(PSP22)]
+    fn transfer(&mut self, to: AccountId, value: Balance, data: Vec<u8>) -> Result<(), PSP22Error> {
+        let caller: AccountId = Self::env().caller();
+        self.transfer_with_fee(caller, to, value, data)
+    }
+
+    #[overrider(PSP22)]
+    fn transfer_from(
+        &mut self,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+        data: Vec<u8>,
+    ) -> Result<(), PSP22Error> {
+        let caller: AccountId = Self::env().caller();
+        let allowance: Balance = psp22::Internal::_allowance(self, &from, &caller);
+        if allowance < value {
+            return Err(PSP22Error::InsufficientAllowance);
+        }
+
+        psp22::Internal::_approve_from_to(self, from, caller, allowance - value)?;
+        self.transfer_with_fee(from, to, value, data)
+    }
+
+    #[default_impl(PSP22Mintable)]
+    #[modifiers(only_role(MINTER))]
+    fn mint() {}
+
+    // Ownership moves in two steps: the owner nominates a new owner here and
+    // it only takes effect once they call accept_ownership, which also hands
+    // over DEFAULT_ADMIN_ROLE.
+    #[overrider(Ownable)]
+    #[modifiers(only_owner)]
+    fn transfer_ownership(&mut self, new_owner: Option<AccountId>) -> Result<(), OwnableError> {
+        if new_owner.is_none() {
+            return Err(OwnableError::NewOwnerIsNotSet);
+        }
+        self.pending_owner.set(&new_owner);
+
+        Ok(())
+    }
+
+    impl FlashLender for Button {
+        #[ink(message)]
+        fn max_flashloan(&self, token: AccountId) -> Balance {
+            if token != self.env().account_id() {
+                return 0;
+            }
+
+            let headroom: Balance =
+                PSP22Capped::cap(self).saturating_sub(PSP22::total_supply(self));
+            self.flash_mint_config().limit.min(headroom)
+        }
+
+        #[ink(message)]
+        fn flash_fee(&self, token: AccountId, amount: Balance) -> Result<Balance, PSP22Error> {
+            if token != self.env().account_id() {
+                return Err(PSP22Error::Custom(String::from("Wrong token")));
+            }
+
+            Ok(Self::basis_points_of(
+                amount,
+                self.flash_mint_config().fee_basis_points,
+            ))
+        }
+
+        // The receiver must approve this contract for amount + fee during
+        // on_flashloan. The amount is burnt and the fee goes to the owner, or
+        // is burnt too when there is none.
+        #[ink(message)]
+        fn flashloan(
+            &mut self,
+            receiver: AccountId,
+            token: AccountId,
+            amount: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error> {
+            let fee: Balance = self.flash_fee(token, amount)?;
+            if amount == 0 || amount > self.max_flashloan(token) {
+                return Err(PSP22Error::Custom(String::from(
+                    "Amount exceeds flash mint limit",
+                )));
+            }
+
+            psp22::Internal::_mint_to(self, receiver, amount)?;
+            let result = build_call::<DefaultEnvironment>()
+                .call(receiver)
+                .gas_limit(0)
+                .call_flags(CallFlags::default().set_allow_reentry(true))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                        "FlashBorrower::on_flashloan"
+                    )))
+                    .push_arg(Self::env().caller())
+                    .push_arg(token)
+                    .push_arg(amount)
+                    .push_arg(fee)
+                    .push_arg(data),
+                )
+                .returns::<Result<(), FlashBorrowerError>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => {}
+                Ok(Ok(Err(FlashBorrowerError::FlashloanRejected(reason)))) => {
+                    return Err(PSP22Error::Custom(reason))
+                }
+                _ => {
+                    return Err(PSP22Error::Custom(String::from(
+                        "Flash borrower call failed",
+                    )))
+                }
+            }
+
+            let lender: AccountId = self.env().account_id();
+            let repayment: Balance = amount + fee;
+            let allowance: Balance = psp22::Internal::_allowance(self, &receiver, &lender);
+            if allowance < repayment {
+                return Err(PSP22Error::Custom(String::from("Flash loan not repaid")));
+            }
+            psp22::Internal::_approve_from_to(self, receiver, lender, allowance - repayment)?;
+            psp22::Internal::_burn_from(self, receiver, amount)?;
+            if fee > 0 {
+                match Ownable::owner(self) {
+                    Some(owner) => {
+                        psp22::Internal::_transfer_from_to(self, receiver, owner, fee, Vec::new())?
+                    }
+                    None => self.burn_tokens(receiver, lender, fee)?,
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Permit for Button {
+        #[ink(message)]
+        fn permit_nonce(&self, owner: AccountId) -> u64 {
+            self.permit_nonces.get(owner).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn permit(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            deadline: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<(), PSP22Error> {
+            if Self::env().block_timestamp() > deadline {
+                return Err(PSP22Error::Custom(String::from("Permit has expired")));
+            }
+
+            // ECDSA accounts are the blake2x256 hash of the compressed public key
+            let nonce: u64 = self.permit_nonce(owner);
+            let message_hash: [u8; 32] = self.env().hash_encoded::<Blake2x256, _>(&(
+                Self::env().account_id(),
+                owner,
+                spender,
+                value,
+                nonce,
+                deadline,
+            ));
+            let signer: Option<AccountId> = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .ok()
+                .map(|public_key| {
+                    AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key))
+                });
+            if signer != Some(owner) {
+                return Err(PSP22Error::Custom(String::from("Invalid signature")));
+            }
+
+            self.permit_nonces.insert(owner, &(nonce + 1));
+            psp22::Internal::_approve_from_to(self, owner, spender, value)
+        }
+    }
+
     impl Button {
         #[ink(constructor)]
         pub fn new(
@@ -72,28 +474,525 @@ pub mod button {
             name: Option<String>,
             symbol: Option<String>,
             decimal: u8,
+        ) -> Self {
+            Self::new_with_initial_supply(cap, cap, name, symbol, decimal)
+        }
+
+        // Mints initial_supply to the deployer. The rest of the cap can be
+        // minted later by accounts the deployer grants MINTER to.
+        #[ink(constructor)]
+        pub fn new_with_initial_supply(
+            cap: Balance,
+            initial_supply: Balance,
+            name: Option<String>,
+            symbol: Option<String>,
+            decimal: u8,
         ) -> Self {
             let mut instance = Self::default();
+            let caller: AccountId = Self::env().caller();
             assert!(capped::Internal::_init_cap(&mut instance, cap).is_ok());
-            assert!(psp22::Internal::_mint_to(&mut instance, Self::env().caller(), cap).is_ok());
+            assert!(psp22::Internal::_mint_to(&mut instance, caller, initial_supply).is_ok());
+            access_control::Internal::_init_with_admin(&mut instance, Some(caller));
+            ownable::Internal::_init_with_owner(&mut instance, caller);
             instance.metadata.name.set(&name);
             instance.metadata.symbol.set(&symbol);
             instance.metadata.decimals.set(&decimal);
             instance
         }
 
+        // === QUERIES ===
+        #[ink(message)]
+        pub fn balance_of_at(&self, account: AccountId, id: u32) -> Result<Balance, PSP22Error> {
+            self.validate_snapshot_id(id)?;
+            let count: u32 = self
+                .account_balance_snapshot_counts
+                .get(account)
+                .unwrap_or_default();
+
+            Ok(Self::value_at(count, id, |index| {
+                self.account_balance_snapshots
+                    .get((account, index))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_else(|| PSP22::balance_of(self, account)))
+        }
+
+        #[ink(message)]
+        pub fn current_snapshot_id(&self) -> u32 {
+            self.snapshot_id.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn delegates(&self, account: AccountId) -> Option<AccountId> {
+            self.delegates.get(account)
+        }
+
+        #[ink(message)]
+        pub fn flash_mint_config(&self) -> FlashMintConfig {
+            self.flash_mint_config.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn get_past_votes(
+            &self,
+            account: AccountId,
+            block: BlockNumber,
+        ) -> Result<Balance, PSP22Error> {
+            if block >= self.env().block_number() {
+                return Err(PSP22Error::Custom(String::from("Block not yet mined")));
+            }
+
+            let count: u32 = self.vote_checkpoint_counts.get(account).unwrap_or_default();
+            let index: u32 = Self::partition_point(count, |index| {
+                self.vote_checkpoints
+                    .get((account, index))
+                    .is_some_and(|(number, _)| number <= block)
+            });
+            if index == 0 {
+                return Ok(0);
+            }
+
+            Ok(self
+                .vote_checkpoints
+                .get((account, index - 1))
+                .map_or(0, |(_, votes)| votes))
+        }
+
+        #[ink(message)]
+        pub fn get_votes(&self, account: AccountId) -> Balance {
+            self.vote_checkpoint_counts
+                .get(account)
+                .and_then(|count| count.checked_sub(1))
+                .and_then(|index| self.vote_checkpoints.get((account, index)))
+                .map_or(0, |(_, votes)| votes)
+        }
+
+        #[ink(message)]
+        pub fn is_pause_exempt(&self, account: AccountId) -> bool {
+            self.pause_exempt.contains(account)
+        }
+
+        #[ink(message)]
+        pub fn is_transfer_fee_exempt(&self, account: AccountId) -> bool {
+            self.transfer_fee_exempt.contains(account)
+        }
+
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn total_burned(&self) -> Balance {
+            self.total_burned.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn total_supply_at(&self, id: u32) -> Result<Balance, PSP22Error> {
+            self.validate_snapshot_id(id)?;
+            let count: u32 = self.total_supply_snapshot_count.get().unwrap_or_default();
+
+            Ok(Self::value_at(count, id, |index| {
+                self.total_supply_snapshots.get(index).unwrap_or_default()
+            })
+            .unwrap_or_else(|| PSP22::total_supply(self)))
+        }
+
+        #[ink(message)]
+        pub fn transfer_fee_config(&self) -> Option<TransferFeeConfig> {
+            self.transfer_fee_config.get().unwrap_or_default()
+        }
+
+        // === HANDLES ===
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<(), OwnableError> {
+            let caller: AccountId = Self::env().caller();
+            if self.pending_owner() != Some(caller) {
+                return Err(OwnableError::CallerIsNotOwner);
+            }
+
+            let previous_owner: Option<AccountId> = Ownable::owner(self);
+            self.ownable.owner.set(&Some(caller));
+            self.pending_owner.set(&None);
+            // Role administration moves with ownership
+            if previous_owner.is_some() {
+                access_control::Internal::_do_revoke_role(self, DEFAULT_ADMIN_ROLE, previous_owner);
+            }
+            access_control::Internal::_setup_role(self, DEFAULT_ADMIN_ROLE, Some(caller));
+            ownable::Internal::_emit_ownership_transferred_event(
+                self,
+                previous_owner,
+                Some(caller),
+            );
+
+            Ok(())
+        }
+
+        // Either every transfer goes through or none do. The total is checked up
+        // front, and any later failure reverts the whole call.
+        #[ink(message)]
+        pub fn batch_transfer(
+            &mut self,
+            transfers: Vec<(AccountId, Balance)>,
+        ) -> Result<(), PSP22Error> {
+            if transfers.len() > MAX_BATCH_TRANSFER_SIZE {
+                return Err(PSP22Error::Custom(String::from("Too many transfers")));
+            }
+            let caller: AccountId = Self::env().caller();
+            let total: Balance = transfers
+                .iter()
+                .try_fold(0, |total: Balance, (_, value)| total.checked_add(*value))
+                .ok_or(PSP22Error::InsufficientBalance)?;
+            if PSP22::balance_of(self, caller) < total {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+
+            for (to, value) in transfers {
+                self.transfer_with_fee(caller, to, value, Vec::new())?;
+            }
+
+            Ok(())
+        }
+
         #[ink(message)]
         pub fn burn(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
             let caller = Self::env().caller();
             if caller != account {
-                let allowance: Balance = psp22::Internal::_allowance(self, &account, &caller);
-                if allowance < amount {
-                    return Err(PSP22Error::InsufficientAllowance);
+                return self.burn_from(account, amount);
+            }
+
+            self.burn_tokens(account, caller, amount)
+        }
+
+        // PSP22Burnable burn_from: always spends the caller's allowance, even
+        // when burning from their own account.
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: AccountId, value: Balance) -> Result<(), PSP22Error> {
+            let caller = Self::env().caller();
+            let allowance: Balance = psp22::Internal::_allowance(self, &from, &caller);
+            if allowance < value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+
+            psp22::Internal::_approve_from_to(self, from, caller, allowance - value)?;
+            self.burn_tokens(from, caller, value)
+        }
+
+        // Balances only count as votes once delegated, which can be to the
+        // holder themselves.
+        #[ink(message)]
+        pub fn delegate(&mut self, delegatee: AccountId) -> Result<(), PSP22Error> {
+            let caller: AccountId = Self::env().caller();
+            let previous_delegate: Option<AccountId> = self.delegates(caller);
+            self.delegates.insert(caller, &delegatee);
+            self.env().emit_event(DelegateChanged {
+                delegator: caller,
+                from_delegate: previous_delegate,
+                to_delegate: Some(delegatee),
+            });
+            self.move_voting_power(
+                previous_delegate,
+                Some(delegatee),
+                PSP22::balance_of(self, caller),
+            );
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_role(PAUSER))]
+        pub fn pause(&mut self) -> Result<(), PSP22Error> {
+            pausable::Internal::_pause(self)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_owner)]
+        pub fn set_cap(&mut self, cap: Balance) -> Result<(), PSP22Error> {
+            if cap < PSP22::total_supply(self) {
+                return Err(PSP22Error::Custom(String::from(
+                    "Cap must be greater than or equal to total supply",
+                )));
+            }
+
+            capped::Internal::_init_cap(self, cap)
+        }
+
+        #[ink(message)]
+        #[modifiers(only_owner)]
+        pub fn set_flash_mint_config(&mut self, config: FlashMintConfig) -> Result<(), PSP22Error> {
+            if config.fee_basis_points > 10_000 {
+                return Err(PSP22Error::Custom(String::from(
+                    "Fee basis points must be less than or equal to 10000",
+                )));
+            }
+
+            self.flash_mint_config.set(&config);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_role(METADATA_ADMIN))]
+        pub fn set_name(&mut self, name: Option<String>) -> Result<(), PSP22Error> {
+            self.metadata.name.set(&name);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_role(PAUSER))]
+        pub fn set_pause_exempt(
+            &mut self,
+            account: AccountId,
+            exempt: bool,
+        ) -> Result<(), PSP22Error> {
+            if exempt {
+                self.pause_exempt.insert(account, &());
+            } else {
+                self.pause_exempt.remove(account);
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_role(METADATA_ADMIN))]
+        pub fn set_symbol(&mut self, symbol: Option<String>) -> Result<(), PSP22Error> {
+            self.metadata.symbol.set(&symbol);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[modifiers(only_owner)]
+        pub fn set_transfer_fee_config(
+            &mut self,
+            config: Option<TransferFeeConfig>,
+        ) -> Result<(), PSP22Error> {
+            if let Some(config) = config {
+                if config.fee_basis_points > 10_000 {
+                    return Err(PSP22Error::Custom(String::from(
+                        "Fee basis points must be less than or equal to 10000",
+                    )));
                 }
+            }
+
+            self.transfer_fee_config.set(&config);
+
+            Ok(())
+        }
 
-                psp22::Internal::_approve_from_to(self, account, caller, allowance - amount)?;
+        #[ink(message)]
+        #[modifiers(only_owner)]
+        pub fn set_transfer_fee_exempt(
+            &mut self,
+            account: AccountId,
+            exempt: bool,
+        ) -> Result<(), PSP22Error> {
+            if exempt {
+                self.transfer_fee_exempt.insert(account, &());
+            } else {
+                self.transfer_fee_exempt.remove(account);
             }
-            psp22::Internal::_burn_from(self, account, amount)
+
+            Ok(())
+        }
+
+        // Balances are only written to a snapshot when they first change after
+        // it, so taking one is cheap.
+        #[ink(message)]
+        #[modifiers(only_owner)]
+        pub fn snapshot(&mut self) -> Result<u32, PSP22Error> {
+            let id: u32 = self.current_snapshot_id() + 1;
+            self.snapshot_id.set(&id);
+            self.env().emit_event(Snapshot { id });
+
+            Ok(id)
+        }
+
+        #[ink(message)]
+        #[modifiers(only_role(PAUSER))]
+        pub fn unpause(&mut self) -> Result<(), PSP22Error> {
+            pausable::Internal::_unpause(self)?;
+
+            Ok(())
+        }
+
+        // === PRIVATE ===
+        // Rounds down and is split so that large amounts can't overflow.
+        fn basis_points_of(amount: Balance, basis_points: u16) -> Balance {
+            let basis_points = Balance::from(basis_points);
+            amount / 10_000 * basis_points + amount % 10_000 * basis_points / 10_000
+        }
+
+        fn burn_tokens(
+            &mut self,
+            account: AccountId,
+            burner: AccountId,
+            amount: Balance,
+        ) -> Result<(), PSP22Error> {
+            psp22::Internal::_burn_from(self, account, amount)?;
+            self.total_burned.set(&(self.total_burned() + amount));
+            self.env().emit_event(Burn {
+                account,
+                burner,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        fn move_voting_power(
+            &mut self,
+            from: Option<AccountId>,
+            to: Option<AccountId>,
+            amount: Balance,
+        ) {
+            if from == to || amount == 0 {
+                return;
+            }
+
+            if let Some(from) = from {
+                let votes: Balance = self.get_votes(from);
+                self.write_vote_checkpoint(from, votes, votes - amount);
+            }
+            if let Some(to) = to {
+                let votes: Balance = self.get_votes(to);
+                self.write_vote_checkpoint(to, votes, votes + amount);
+            }
+        }
+
+        // The sender pays value and the recipient receives value less the fee,
+        // unless either of them is exempt or is the treasury.
+        fn transfer_with_fee(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error> {
+            let fee: Option<(AccountId, Balance)> = self
+                .transfer_fee_config()
+                .filter(|config| {
+                    ![from, to].iter().any(|account| {
+                        *account == config.treasury || self.is_transfer_fee_exempt(*account)
+                    })
+                })
+                .map(|config| {
+                    (
+                        config.treasury,
+                        Self::basis_points_of(value, config.fee_basis_points),
+                    )
+                });
+            let fee_amount: Balance = fee.map_or(0, |(_, amount)| amount);
+            psp22::Internal::_transfer_from_to(self, from, to, value - fee_amount, data)?;
+            if let Some((treasury, amount)) = fee.filter(|(_, amount)| *amount > 0) {
+                psp22::Internal::_transfer_from_to(self, from, treasury, amount, Vec::new())?;
+            }
+
+            Ok(())
+        }
+
+        fn update_account_balance_snapshot(&mut self, account: AccountId) {
+            let id: u32 = self.current_snapshot_id();
+            let count: u32 = self
+                .account_balance_snapshot_counts
+                .get(account)
+                .unwrap_or_default();
+            let last_id: u32 = count
+                .checked_sub(1)
+                .and_then(|index| self.account_balance_snapshots.get((account, index)))
+                .map_or(0, |(id, _)| id);
+            if id > last_id {
+                let balance: Balance = PSP22::balance_of(self, account);
+                self.account_balance_snapshots
+                    .insert((account, count), &(id, balance));
+                self.account_balance_snapshot_counts
+                    .insert(account, &(count + 1));
+            }
+        }
+
+        fn update_total_supply_snapshot(&mut self) {
+            let id: u32 = self.current_snapshot_id();
+            let count: u32 = self.total_supply_snapshot_count.get().unwrap_or_default();
+            let last_id: u32 = count
+                .checked_sub(1)
+                .and_then(|index| self.total_supply_snapshots.get(index))
+                .map_or(0, |(id, _)| id);
+            if id > last_id {
+                let total_supply: Balance = PSP22::total_supply(self);
+                self.total_supply_snapshots
+                    .insert(count, &(id, total_supply));
+                self.total_supply_snapshot_count.set(&(count + 1));
+            }
+        }
+
+        fn write_vote_checkpoint(
+            &mut self,
+            delegate: AccountId,
+            previous_votes: Balance,
+            new_votes: Balance,
+        ) {
+            let block: BlockNumber = self.env().block_number();
+            let count: u32 = self
+                .vote_checkpoint_counts
+                .get(delegate)
+                .unwrap_or_default();
+            let last_block: Option<BlockNumber> = count
+                .checked_sub(1)
+                .and_then(|index| self.vote_checkpoints.get((delegate, index)))
+                .map(|(number, _)| number);
+            // Changes within a block overwrite that block's checkpoint
+            if last_block == Some(block) {
+                self.vote_checkpoints
+                    .insert((delegate, count - 1), &(block, new_votes));
+            } else {
+                self.vote_checkpoints
+                    .insert((delegate, count), &(block, new_votes));
+                self.vote_checkpoint_counts.insert(delegate, &(count + 1));
+            }
+            self.env().emit_event(DelegateVotesChanged {
+                delegate,
+                previous_votes,
+                new_votes,
+            });
+        }
+
+        fn validate_snapshot_id(&self, id: u32) -> Result<(), PSP22Error> {
+            if id == 0 || id > self.current_snapshot_id() {
+                return Err(PSP22Error::Custom(String::from("Snapshot not found")));
+            }
+
+            Ok(())
+        }
+
+        // Index of the first of count entries for which pred is false, given
+        // that pred is true for every entry before it.
+        fn partition_point(count: u32, pred: impl Fn(u32) -> bool) -> u32 {
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let mid: u32 = low + (high - low) / 2;
+                if pred(mid) {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+
+            low
+        }
+
+        // The value at a snapshot is the first one recorded at or after it. If
+        // there is none, the value hasn't changed since.
+        fn value_at(
+            count: u32,
+            id: u32,
+            snapshot: impl Fn(u32) -> (u32, Balance),
+        ) -> Option<Balance> {
+            let index: u32 = Self::partition_point(count, |index| snapshot(index).0 < id);
+            (index < count).then(|| snapshot(index).1)
         }
     }
 
@@ -101,16 +1000,39 @@ pub mod button {
     mod tests {
         use super::*;
         use ink::env::{
-            test::{default_accounts, set_caller, DefaultAccounts},
+            hash::{Blake2x256, CryptoHash, HashOutput},
+            test::{
+                advance_block, callee, default_accounts, set_block_timestamp, set_caller,
+                DefaultAccounts,
+            },
             DefaultEnvironment,
         };
+        use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+        // === CONSTANTS ===
+        const MOCK_CAP: Balance = 28_000_000_000_000;
 
         // === HELPERS ===
         fn init() -> (DefaultAccounts<DefaultEnvironment>, Button) {
             let accounts = default_accounts();
             set_caller::<DefaultEnvironment>(accounts.bob);
             let az_button = Button::new(
-                28_000_000_000_000,
+                MOCK_CAP,
+                Some("Button".to_string()),
+                Some("BTN".to_string()),
+                6,
+            );
+            (accounts, az_button)
+        }
+
+        fn init_with_initial_supply(
+            initial_supply: Balance,
+        ) -> (DefaultAccounts<DefaultEnvironment>, Button) {
+            let accounts = default_accounts();
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let az_button = Button::new_with_initial_supply(
+                MOCK_CAP,
+                initial_supply,
                 Some("Button".to_string()),
                 Some("BTN".to_string()),
                 6,
@@ -118,7 +1040,251 @@ pub mod button {
             (accounts, az_button)
         }
 
+        fn blake2x256(input: &[u8]) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            <Blake2x256 as CryptoHash>::hash(input, &mut output);
+            output
+        }
+
+        // Returns the ECDSA owner account and a permit signed by it.
+        fn mock_permit(
+            spender: AccountId,
+            value: Balance,
+            nonce: u64,
+            deadline: Timestamp,
+        ) -> (AccountId, [u8; 65]) {
+            let secp = Secp256k1::new();
+            let secret_key = SecretKey::from_slice(&[7; 32]).unwrap();
+            let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+            let owner = AccountId::from(blake2x256(&public_key.serialize()));
+            let message_hash = blake2x256(&scale::Encode::encode(&(
+                callee::<DefaultEnvironment>(),
+                owner,
+                spender,
+                value,
+                nonce,
+                deadline,
+            )));
+            let (recovery_id, compact) = secp
+                .sign_ecdsa_recoverable(&Message::from_slice(&message_hash).unwrap(), &secret_key)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            (owner, signature)
+        }
+
+        // === TEST CONSTRUCTORS ===
+        #[ink::test]
+        fn test_new_with_initial_supply() {
+            let (accounts, az_button) = init_with_initial_supply(1_000_000_000_000);
+            // * it mints the initial supply to the deployer
+            assert_eq!(
+                PSP22::balance_of(&az_button, accounts.bob),
+                1_000_000_000_000
+            );
+            assert_eq!(PSP22::total_supply(&az_button), 1_000_000_000_000);
+            // * it sets the cap
+            assert_eq!(PSP22Capped::cap(&az_button), MOCK_CAP);
+            // * it makes the deployer the admin
+            assert!(AccessControl::has_role(
+                &az_button,
+                DEFAULT_ADMIN_ROLE,
+                Some(accounts.bob)
+            ));
+            assert!(!AccessControl::has_role(
+                &az_button,
+                MINTER,
+                Some(accounts.bob)
+            ));
+            // * it makes the deployer the owner
+            assert_eq!(Ownable::owner(&az_button), Some(accounts.bob));
+            assert_eq!(az_button.pending_owner(), None);
+        }
+
+        // === TEST QUERIES ===
+        #[ink::test]
+        fn test_flash_fee() {
+            let (accounts, mut az_button) = init();
+            let token: AccountId = callee::<DefaultEnvironment>();
+            // when token is not this token
+            // * it raises an error
+            let result = az_button.flash_fee(accounts.alice, 1_000);
+            assert_eq!(result, Err(PSP22Error::Custom(String::from("Wrong token"))));
+            // when token is this token
+            // * it charges the fee in basis points, rounded down
+            assert_eq!(az_button.flash_fee(token, 1_000), Ok(0));
+            az_button
+                .set_flash_mint_config(FlashMintConfig {
+                    limit: 0,
+                    fee_basis_points: 9,
+                })
+                .unwrap();
+            assert_eq!(az_button.flash_fee(token, 1_000_000), Ok(900));
+            assert_eq!(az_button.flash_fee(token, 1_111), Ok(0));
+            assert_eq!(
+                az_button.flash_fee(token, Balance::MAX),
+                Ok(Balance::MAX / 10_000 * 9 + Balance::MAX % 10_000 * 9 / 10_000)
+            );
+        }
+
+        #[ink::test]
+        fn test_max_flashloan() {
+            let (accounts, mut az_button) = init_with_initial_supply(MOCK_CAP - 1_000);
+            let token: AccountId = callee::<DefaultEnvironment>();
+            // when flash minting is disabled
+            // * it returns zero
+            assert_eq!(az_button.max_flashloan(token), 0);
+            // when flash minting is enabled
+            // * it returns the limit
+            az_button
+                .set_flash_mint_config(FlashMintConfig {
+                    limit: 500,
+                    fee_basis_points: 0,
+                })
+                .unwrap();
+            assert_eq!(az_button.max_flashloan(token), 500);
+            // * it returns the room left under the cap when that is smaller
+            az_button
+                .set_flash_mint_config(FlashMintConfig {
+                    limit: 5_000,
+                    fee_basis_points: 0,
+                })
+                .unwrap();
+            assert_eq!(az_button.max_flashloan(token), 1_000);
+            // * it returns zero for other tokens
+            assert_eq!(az_button.max_flashloan(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn test_get_past_votes() {
+            let (accounts, mut az_button) = init_with_initial_supply(1_000);
+            // when block has not been mined
+            // * it raises an error
+            let mut result = az_button.get_past_votes(accounts.bob, 0);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Block not yet mined")))
+            );
+            // when block has been mined
+            // * it returns the votes at the end of that block
+            az_button.delegate(accounts.bob).unwrap(); // block 0: 1_000
+            PSP22::transfer(&mut az_button, accounts.charlie, 100, vec![]).unwrap(); // block 0: 900
+            advance_block::<DefaultEnvironment>();
+            advance_block::<DefaultEnvironment>();
+            PSP22::transfer(&mut az_button, accounts.charlie, 200, vec![]).unwrap(); // block 2: 700
+            advance_block::<DefaultEnvironment>();
+            result = az_button.get_past_votes(accounts.bob, 0);
+            assert_eq!(result, Ok(900));
+            result = az_button.get_past_votes(accounts.bob, 1);
+            assert_eq!(result, Ok(900));
+            result = az_button.get_past_votes(accounts.bob, 2);
+            assert_eq!(result, Ok(700));
+            // * it returns zero for accounts without votes
+            result = az_button.get_past_votes(accounts.charlie, 2);
+            assert_eq!(result, Ok(0));
+            // when there are many checkpoints
+            // * it finds the one for each block
+            for _ in 0..10 {
+                PSP22::transfer(&mut az_button, accounts.charlie, 10, vec![]).unwrap(); // block 3..=12
+                advance_block::<DefaultEnvironment>();
+            }
+            for i in 0..10 {
+                result = az_button.get_past_votes(accounts.bob, 3 + i);
+                assert_eq!(result, Ok(700 - 10 * Balance::from(i + 1)));
+            }
+        }
+
         // === TEST HANDLES ===
+        #[ink::test]
+        fn test_accept_ownership() {
+            let (accounts, mut az_button) = init();
+            // when there is no pending owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = az_button.accept_ownership();
+            assert_eq!(result, Err(OwnableError::CallerIsNotOwner));
+            // when there is a pending owner
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            Ownable::transfer_ownership(&mut az_button, Some(accounts.alice)).unwrap();
+            // = when called by someone other than the pending owner
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            result = az_button.accept_ownership();
+            assert_eq!(result, Err(OwnableError::CallerIsNotOwner));
+            // = when called by the pending owner
+            // = * it transfers ownership and clears the pending owner
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_button.accept_ownership().unwrap();
+            assert_eq!(Ownable::owner(&az_button), Some(accounts.alice));
+            assert_eq!(az_button.pending_owner(), None);
+            // = * it hands role administration to the new owner
+            assert!(AccessControl::has_role(
+                &az_button,
+                DEFAULT_ADMIN_ROLE,
+                Some(accounts.alice)
+            ));
+            assert!(!AccessControl::has_role(
+                &az_button,
+                DEFAULT_ADMIN_ROLE,
+                Some(accounts.bob)
+            ));
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.bob)),
+                Err(AccessControlError::MissingRole)
+            );
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.charlie)).unwrap();
+            AccessControl::revoke_role(&mut az_button, MINTER, Some(accounts.charlie)).unwrap();
+        }
+
+        #[ink::test]
+        fn test_batch_transfer() {
+            let (accounts, mut az_button) = init_with_initial_supply(1_000);
+            // when there are too many transfers
+            // * it raises an error
+            let mut result =
+                az_button.batch_transfer(vec![(accounts.alice, 1); MAX_BATCH_TRANSFER_SIZE + 1]);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Too many transfers")))
+            );
+            // when one entry can not be covered by the balance
+            // * it raises an error and transfers nothing
+            result = az_button.batch_transfer(vec![
+                (accounts.alice, 400),
+                (accounts.charlie, 400),
+                (accounts.django, 201),
+            ]);
+            assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+            for account in [accounts.alice, accounts.charlie, accounts.django] {
+                assert_eq!(PSP22::balance_of(&az_button, account), 0);
+            }
+            assert_eq!(PSP22::balance_of(&az_button, accounts.bob), 1_000);
+            // when the total overflows
+            // * it raises an error
+            result = az_button
+                .batch_transfer(vec![(accounts.alice, Balance::MAX), (accounts.charlie, 1)]);
+            assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+            // when the balance covers every entry
+            // * it makes each transfer and emits a Transfer event for each
+            let events_before: usize = ink::env::test::recorded_events().count();
+            az_button
+                .batch_transfer(vec![
+                    (accounts.alice, 400),
+                    (accounts.charlie, 400),
+                    (accounts.django, 200),
+                ])
+                .unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.alice), 400);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.charlie), 400);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 200);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.bob), 0);
+            assert_eq!(ink::env::test::recorded_events().count() - events_before, 3);
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS STORAGE IS NOT REVERTED OFF-CHAIN
+        }
+
         #[ink::test]
         fn test_burn() {
             let (accounts, mut az_button) = init();
@@ -153,6 +1319,627 @@ pub mod button {
             // === when balance is insufficient
             result = az_button.burn(accounts.bob, 27_000_000_000_000);
             assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+            // * it tracks the total burned
+            assert_eq!(az_button.total_burned(), 2_000_000_000_000);
+            assert_eq!(PSP22::total_supply(&az_button), 26_000_000_000_000);
+            // * it emits a Burn event for each burn
+            let burn_events: usize = ink::env::test::recorded_events()
+                .filter(|event| {
+                    <Event as scale::Decode>::decode(&mut &event.data[..])
+                        .map(|event| matches!(event, Event::Burn(_)))
+                        .unwrap_or(false)
+                })
+                .count();
+            assert_eq!(burn_events, 2);
+        }
+
+        #[ink::test]
+        fn test_burn_from() {
+            let (accounts, mut az_button) = init();
+            // when allowance is insufficient
+            // * it raises an error, even when burning from own account
+            let mut result = az_button.burn_from(accounts.bob, 1_000);
+            assert_eq!(result, Err(PSP22Error::InsufficientAllowance));
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            result = az_button.burn_from(accounts.bob, 1_000);
+            assert_eq!(result, Err(PSP22Error::InsufficientAllowance));
+            // when allowance is sufficient
+            // * it burns the amount, decreases the allowance and tracks the total burned
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            PSP22::increase_allowance(&mut az_button, accounts.alice, 1_500).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_button.burn_from(accounts.bob, 1_000).unwrap();
+            assert_eq!(
+                PSP22::balance_of(&az_button, accounts.bob),
+                MOCK_CAP - 1_000
+            );
+            assert_eq!(
+                PSP22::allowance(&az_button, accounts.bob, accounts.alice),
+                500
+            );
+            assert_eq!(az_button.total_burned(), 1_000);
+        }
+
+        #[ink::test]
+        fn test_delegate() {
+            let (accounts, mut az_button) = init_with_initial_supply(1_000);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.bob)).unwrap();
+            // when balances have not been delegated
+            // * they do not count as votes
+            assert_eq!(az_button.get_votes(accounts.bob), 0);
+            // when delegating to self
+            // * it gives the delegator votes equal to their balance
+            az_button.delegate(accounts.bob).unwrap();
+            assert_eq!(az_button.delegates(accounts.bob), Some(accounts.bob));
+            assert_eq!(az_button.get_votes(accounts.bob), 1_000);
+            // when delegating to someone else
+            // * it moves the votes to the new delegate
+            az_button.delegate(accounts.alice).unwrap();
+            assert_eq!(az_button.get_votes(accounts.bob), 0);
+            assert_eq!(az_button.get_votes(accounts.alice), 1_000);
+            // when transferring to an account without a delegate
+            // * it removes the votes from the sender's delegate
+            PSP22::transfer(&mut az_button, accounts.charlie, 100, vec![]).unwrap();
+            assert_eq!(az_button.get_votes(accounts.alice), 900);
+            // when transferring to an account with a delegate
+            // * it moves the votes to the recipient's delegate
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            az_button.delegate(accounts.django).unwrap();
+            assert_eq!(az_button.get_votes(accounts.django), 100);
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            PSP22::transfer(&mut az_button, accounts.charlie, 200, vec![]).unwrap();
+            assert_eq!(az_button.get_votes(accounts.alice), 700);
+            assert_eq!(az_button.get_votes(accounts.django), 300);
+            // when minting
+            // * it adds votes to the recipient's delegate
+            PSP22Mintable::mint(&mut az_button, accounts.charlie, 50).unwrap();
+            assert_eq!(az_button.get_votes(accounts.django), 350);
+            // when burning with an allowance
+            // * it removes votes from the burnt account's delegate
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            PSP22::increase_allowance(&mut az_button, accounts.eve, 50).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.eve);
+            az_button.burn(accounts.charlie, 50).unwrap();
+            assert_eq!(az_button.get_votes(accounts.django), 300);
+            assert_eq!(az_button.get_votes(accounts.eve), 0);
+            // when burning own balance
+            // * it removes votes from own delegate
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            az_button.burn(accounts.bob, 100).unwrap();
+            assert_eq!(az_button.get_votes(accounts.alice), 600);
+        }
+
+        #[ink::test]
+        fn test_flashloan() {
+            let (accounts, mut az_button) = init_with_initial_supply(0);
+            let token: AccountId = callee::<DefaultEnvironment>();
+            // when token is not this token
+            // * it raises an error
+            let mut result = az_button.flashloan(accounts.django, accounts.alice, 1_000, vec![]);
+            assert_eq!(result, Err(PSP22Error::Custom(String::from("Wrong token"))));
+            // when flash minting is disabled
+            // * it raises an error
+            result = az_button.flashloan(accounts.django, token, 1_000, vec![]);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from(
+                    "Amount exceeds flash mint limit"
+                )))
+            );
+            // when amount is over the limit
+            // * it raises an error
+            az_button
+                .set_flash_mint_config(FlashMintConfig {
+                    limit: 999,
+                    fee_basis_points: 0,
+                })
+                .unwrap();
+            result = az_button.flashloan(accounts.django, token, 1_000, vec![]);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from(
+                    "Amount exceeds flash mint limit"
+                )))
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS THE BORROWER SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_grant_and_revoke_minter() {
+            let (accounts, mut az_button) = init_with_initial_supply(0);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result =
+                AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.alice));
+            assert_eq!(result, Err(AccessControlError::MissingRole));
+            // when called by admin
+            // * it grants and revokes the minter role
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.alice)).unwrap();
+            assert!(AccessControl::has_role(
+                &az_button,
+                MINTER,
+                Some(accounts.alice)
+            ));
+            result = AccessControl::revoke_role(&mut az_button, MINTER, Some(accounts.alice));
+            assert!(result.is_ok());
+            assert!(!AccessControl::has_role(
+                &az_button,
+                MINTER,
+                Some(accounts.alice)
+            ));
+        }
+
+        #[ink::test]
+        fn test_mint() {
+            let (accounts, mut az_button) = init_with_initial_supply(MOCK_CAP - 1_000);
+            // when caller is not a minter
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = PSP22Mintable::mint(&mut az_button, accounts.alice, 1_000);
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            // when caller is a minter
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.alice)).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when amount would exceed the cap
+            // = * it raises an error
+            result = PSP22Mintable::mint(&mut az_button, accounts.charlie, 1_001);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Cap exceeded")))
+            );
+            // = when amount is within the cap
+            // = * it mints the amount
+            PSP22Mintable::mint(&mut az_button, accounts.charlie, 1_000).unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.charlie), 1_000);
+            assert_eq!(PSP22::total_supply(&az_button), MOCK_CAP);
+        }
+
+        #[ink::test]
+        fn test_pause_and_unpause() {
+            let (accounts, mut az_button) = init_with_initial_supply(0);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.bob)).unwrap();
+            // when caller is not a pauser
+            // * it raises an error
+            let mut result = az_button.pause();
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            // when caller is a pauser
+            AccessControl::grant_role(&mut az_button, PAUSER, Some(accounts.alice)).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // * it pauses
+            az_button.pause().unwrap();
+            assert!(Pausable::paused(&az_button));
+            // = when paused
+            // = * it stops minting
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            result = PSP22Mintable::mint(&mut az_button, accounts.bob, 1_000);
+            assert_eq!(result, Err(PSP22Error::from(PausableError::Paused)));
+            // = * it can not be paused again
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            result = az_button.pause();
+            assert_eq!(result, Err(PSP22Error::from(PausableError::Paused)));
+            // * it unpauses
+            az_button.unpause().unwrap();
+            assert!(!Pausable::paused(&az_button));
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            PSP22Mintable::mint(&mut az_button, accounts.bob, 1_000).unwrap();
+            // when unpausing as a non-pauser
+            // * it raises an error
+            result = az_button.unpause();
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+        }
+
+        #[ink::test]
+        fn test_permit() {
+            let (accounts, mut az_button) = init();
+            let deadline: Timestamp = 10;
+            let (owner, signature) = mock_permit(accounts.alice, 1_000, 0, deadline);
+            // when deadline has passed
+            // * it raises an error
+            set_block_timestamp::<DefaultEnvironment>(deadline + 1);
+            let mut result = az_button.permit(owner, accounts.alice, 1_000, deadline, signature);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Permit has expired")))
+            );
+            set_block_timestamp::<DefaultEnvironment>(deadline);
+            // when signature does not match the permit
+            // * it raises an error
+            result = az_button.permit(owner, accounts.alice, 1_001, deadline, signature);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Invalid signature")))
+            );
+            result = az_button.permit(owner, accounts.alice, 1_000, deadline, [0; 65]);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Invalid signature")))
+            );
+            // when signature is from the owner
+            // * it approves the spender and increases the nonce
+            az_button
+                .permit(owner, accounts.alice, 1_000, deadline, signature)
+                .unwrap();
+            assert_eq!(PSP22::allowance(&az_button, owner, accounts.alice), 1_000);
+            assert_eq!(az_button.permit_nonce(owner), 1);
+            // when the permit is replayed
+            // * it raises an error
+            result = az_button.permit(owner, accounts.alice, 1_000, deadline, signature);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from("Invalid signature")))
+            );
+        }
+
+        #[ink::test]
+        fn test_set_cap() {
+            let (accounts, mut az_button) = init_with_initial_supply(1_000);
+            // when called by non-owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = az_button.set_cap(2_000);
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(OwnableError::CallerIsNotOwner))
+            );
+            // when called by owner
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            // = when cap is below total supply
+            // = * it raises an error
+            result = az_button.set_cap(999);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from(
+                    "Cap must be greater than or equal to total supply"
+                )))
+            );
+            // = when cap is greater than or equal to total supply
+            // = * it sets the cap
+            az_button.set_cap(1_000).unwrap();
+            assert_eq!(PSP22Capped::cap(&az_button), 1_000);
+        }
+
+        #[ink::test]
+        fn test_set_flash_mint_config() {
+            let (accounts, mut az_button) = init();
+            let mut config = FlashMintConfig {
+                limit: 1_000,
+                fee_basis_points: 10_001,
+            };
+            // when called by non-owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = az_button.set_flash_mint_config(config);
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(OwnableError::CallerIsNotOwner))
+            );
+            // when called by owner
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            // = when fee is over 100%
+            // = * it raises an error
+            result = az_button.set_flash_mint_config(config);
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from(
+                    "Fee basis points must be less than or equal to 10000"
+                )))
+            );
+            // = when fee is valid
+            // = * it sets the config
+            config.fee_basis_points = 10_000;
+            az_button.set_flash_mint_config(config).unwrap();
+            assert_eq!(az_button.flash_mint_config(), config);
+        }
+
+        #[ink::test]
+        fn test_set_name_and_symbol() {
+            let (accounts, mut az_button) = init();
+            // when caller is not a metadata admin
+            // * it raises an error
+            let mut result = az_button.set_name(Some("Button 2".to_string()));
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            result = az_button.set_symbol(Some("BTN2".to_string()));
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            // when caller is a metadata admin
+            // * it sets the name and symbol
+            AccessControl::grant_role(&mut az_button, METADATA_ADMIN, Some(accounts.alice))
+                .unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_button.set_name(Some("Button 2".to_string())).unwrap();
+            az_button.set_symbol(Some("BTN2".to_string())).unwrap();
+            assert_eq!(
+                PSP22Metadata::token_name(&az_button),
+                Some("Button 2".to_string())
+            );
+            assert_eq!(
+                PSP22Metadata::token_symbol(&az_button),
+                Some("BTN2".to_string())
+            );
+        }
+
+        #[ink::test]
+        fn test_set_pause_exempt() {
+            let (accounts, mut az_button) = init();
+            // when caller is not a pauser
+            // * it raises an error
+            let result = az_button.set_pause_exempt(accounts.alice, true);
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(AccessControlError::MissingRole))
+            );
+            // when caller is a pauser
+            // * it adds and removes the exemption
+            AccessControl::grant_role(&mut az_button, PAUSER, Some(accounts.bob)).unwrap();
+            az_button.set_pause_exempt(accounts.alice, true).unwrap();
+            assert!(az_button.is_pause_exempt(accounts.alice));
+            az_button.set_pause_exempt(accounts.alice, false).unwrap();
+            assert!(!az_button.is_pause_exempt(accounts.alice));
+        }
+
+        #[ink::test]
+        fn test_snapshot() {
+            let (accounts, mut az_button) = init();
+            // when called by non-owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let result = az_button.snapshot();
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(OwnableError::CallerIsNotOwner))
+            );
+            // when called by owner
+            // * it returns incrementing ids
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(az_button.snapshot(), Ok(1));
+            assert_eq!(az_button.snapshot(), Ok(2));
+            assert_eq!(az_button.current_snapshot_id(), 2);
+        }
+
+        #[ink::test]
+        fn test_snapshot_balances() {
+            let (accounts, mut az_button) = init_with_initial_supply(1_000_000);
+            AccessControl::grant_role(&mut az_button, MINTER, Some(accounts.bob)).unwrap();
+            // when snapshot does not exist
+            // * it raises an error
+            for id in [0, 1] {
+                assert_eq!(
+                    az_button.balance_of_at(accounts.bob, id),
+                    Err(PSP22Error::Custom(String::from("Snapshot not found")))
+                );
+                assert_eq!(
+                    az_button.total_supply_at(id),
+                    Err(PSP22Error::Custom(String::from("Snapshot not found")))
+                );
+            }
+
+            // when balances change over many snapshots
+            // * it returns balances and total supply as they were at each snapshot
+            let holders: [AccountId; 3] = [accounts.bob, accounts.charlie, accounts.django];
+            let mut expected_balances: Vec<[Balance; 3]> = vec![];
+            let mut expected_total_supplies: Vec<Balance> = vec![];
+            for round in 0..10_u128 {
+                let id: u32 = az_button.snapshot().unwrap();
+                assert_eq!(id, round as u32 + 1);
+                expected_balances.push(holders.map(|holder| PSP22::balance_of(&az_button, holder)));
+                expected_total_supplies.push(PSP22::total_supply(&az_button));
+                // skip some rounds so that snapshots without changes are covered
+                if round % 3 == 2 {
+                    continue;
+                }
+
+                set_caller::<DefaultEnvironment>(accounts.bob);
+                PSP22::transfer(&mut az_button, accounts.charlie, 1_000 + round, vec![]).unwrap();
+                PSP22::transfer(&mut az_button, accounts.django, 500, vec![]).unwrap();
+                set_caller::<DefaultEnvironment>(accounts.charlie);
+                PSP22::transfer(&mut az_button, accounts.django, 100 * round, vec![]).unwrap();
+                set_caller::<DefaultEnvironment>(accounts.django);
+                az_button.burn(accounts.django, 10).unwrap();
+                set_caller::<DefaultEnvironment>(accounts.bob);
+                PSP22Mintable::mint(&mut az_button, accounts.charlie, 20).unwrap();
+            }
+            for (index, balances) in expected_balances.iter().enumerate() {
+                let id: u32 = index as u32 + 1;
+                for (holder, balance) in holders.iter().zip(balances) {
+                    assert_eq!(az_button.balance_of_at(*holder, id), Ok(*balance));
+                }
+                assert_eq!(
+                    az_button.total_supply_at(id),
+                    Ok(expected_total_supplies[index])
+                );
+            }
+            // * it does not change the current balances
+            assert_eq!(PSP22::total_supply(&az_button), 1_000_000 + 7 * (20 - 10));
+            // * it returns current values for an account that never changed
+            assert_eq!(az_button.balance_of_at(accounts.eve, 5), Ok(0));
+        }
+
+        #[ink::test]
+        fn test_transfer() {
+            let (accounts, mut az_button) = init();
+            PSP22::transfer(&mut az_button, accounts.charlie, 1_000, vec![]).unwrap();
+            AccessControl::grant_role(&mut az_button, PAUSER, Some(accounts.bob)).unwrap();
+            az_button.pause().unwrap();
+            // when paused
+            // = when caller, sender and recipient are not exempt
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            let mut result = PSP22::transfer(&mut az_button, accounts.django, 100, vec![]);
+            assert_eq!(result, Err(PSP22Error::from(PausableError::Paused)));
+            result = az_button.burn(accounts.charlie, 100);
+            assert_eq!(result, Err(PSP22Error::from(PausableError::Paused)));
+            // = when recipient is exempt
+            // = * it transfers
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            az_button.set_pause_exempt(accounts.alice, true).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            PSP22::transfer(&mut az_button, accounts.alice, 100, vec![]).unwrap();
+            // = when sender is exempt
+            // = * it transfers
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            PSP22::transfer(&mut az_button, accounts.django, 50, vec![]).unwrap();
+            // = when caller is exempt
+            // = * it transfers on behalf of others
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            PSP22::increase_allowance(&mut az_button, accounts.alice, 100).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            PSP22::transfer_from(
+                &mut az_button,
+                accounts.charlie,
+                accounts.django,
+                100,
+                vec![],
+            )
+            .unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 150);
+            // when unpaused
+            // * it transfers
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            az_button.unpause().unwrap();
+            set_caller::<DefaultEnvironment>(accounts.django);
+            PSP22::transfer(&mut az_button, accounts.eve, 150, vec![]).unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.eve), 150);
+        }
+
+        #[ink::test]
+        fn test_set_transfer_fee_config() {
+            let (accounts, mut az_button) = init();
+            let mut config = TransferFeeConfig {
+                treasury: accounts.django,
+                fee_basis_points: 10_001,
+            };
+            // when called by non-owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = az_button.set_transfer_fee_config(Some(config));
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(OwnableError::CallerIsNotOwner))
+            );
+            result = az_button.set_transfer_fee_exempt(accounts.alice, true);
+            assert_eq!(
+                result,
+                Err(PSP22Error::from(OwnableError::CallerIsNotOwner))
+            );
+            // when called by owner
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            // = when fee is over 100%
+            // = * it raises an error
+            result = az_button.set_transfer_fee_config(Some(config));
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(String::from(
+                    "Fee basis points must be less than or equal to 10000"
+                )))
+            );
+            // = when fee is valid
+            // = * it sets the config and exemptions
+            config.fee_basis_points = 100;
+            az_button.set_transfer_fee_config(Some(config)).unwrap();
+            assert_eq!(az_button.transfer_fee_config(), Some(config));
+            az_button
+                .set_transfer_fee_exempt(accounts.alice, true)
+                .unwrap();
+            assert!(az_button.is_transfer_fee_exempt(accounts.alice));
+            az_button
+                .set_transfer_fee_exempt(accounts.alice, false)
+                .unwrap();
+            assert!(!az_button.is_transfer_fee_exempt(accounts.alice));
+            az_button.set_transfer_fee_config(None).unwrap();
+            assert_eq!(az_button.transfer_fee_config(), None);
+        }
+
+        #[ink::test]
+        fn test_transfer_with_fee() {
+            let (accounts, mut az_button) = init();
+            az_button
+                .set_transfer_fee_config(Some(TransferFeeConfig {
+                    treasury: accounts.django,
+                    fee_basis_points: 100,
+                }))
+                .unwrap();
+            // when neither party is exempt
+            // * it sends the fee to the treasury
+            PSP22::transfer(&mut az_button, accounts.charlie, 10_000, vec![]).unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.charlie), 9_900);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 100);
+            assert_eq!(
+                PSP22::balance_of(&az_button, accounts.bob),
+                MOCK_CAP - 10_000
+            );
+            // * it takes the fee on transfer_from
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            PSP22::increase_allowance(&mut az_button, accounts.alice, 5_000).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            PSP22::transfer_from(
+                &mut az_button,
+                accounts.charlie,
+                accounts.eve,
+                5_000,
+                vec![],
+            )
+            .unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.eve), 4_950);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 150);
+            assert_eq!(
+                PSP22::allowance(&az_button, accounts.charlie, accounts.alice),
+                0
+            );
+            // when sender or recipient is exempt
+            // * it does not take a fee
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            az_button
+                .set_transfer_fee_exempt(accounts.eve, true)
+                .unwrap();
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            PSP22::transfer(&mut az_button, accounts.eve, 4_900, vec![]).unwrap();
+            set_caller::<DefaultEnvironment>(accounts.eve);
+            PSP22::transfer(&mut az_button, accounts.frank, 1_000, vec![]).unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.frank), 1_000);
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 150);
+            // when recipient is the treasury
+            // * it does not take a fee
+            set_caller::<DefaultEnvironment>(accounts.frank);
+            PSP22::transfer(&mut az_button, accounts.django, 1_000, vec![]).unwrap();
+            assert_eq!(PSP22::balance_of(&az_button, accounts.django), 1_150);
+        }
+
+        #[ink::test]
+        fn test_transfer_ownership() {
+            let (accounts, mut az_button) = init();
+            // when called by non-owner
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut result = Ownable::transfer_ownership(&mut az_button, Some(accounts.alice));
+            assert_eq!(result, Err(OwnableError::CallerIsNotOwner));
+            // when called by owner
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            // = when new owner is not set
+            // = * it raises an error
+            result = Ownable::transfer_ownership(&mut az_button, None);
+            assert_eq!(result, Err(OwnableError::NewOwnerIsNotSet));
+            // = when new owner is set
+            // = * it nominates the pending owner without changing the owner
+            Ownable::transfer_ownership(&mut az_button, Some(accounts.alice)).unwrap();
+            assert_eq!(az_button.pending_owner(), Some(accounts.alice));
+            assert_eq!(Ownable::owner(&az_button), Some(accounts.bob));
         }
     }
 }
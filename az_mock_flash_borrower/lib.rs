@@ -0,0 +1,56 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::az_mock_flash_borrower::MockFlashBorrowerRef;
+
+// Sample flash mint borrower for e2e tests. It only accepts loans from the
+// token it was set up with and approves repayment when `repay` is set.
+#[ink::contract]
+pub mod az_mock_flash_borrower {
+    use az_button::{FlashBorrower, FlashBorrowerError};
+    use ink::{
+        env::CallFlags,
+        prelude::{string::ToString, vec::Vec},
+    };
+    use openbrush::contracts::psp22::PSP22Ref;
+
+    #[ink(storage)]
+    pub struct MockFlashBorrower {
+        lender: AccountId,
+        repay: bool,
+    }
+    impl MockFlashBorrower {
+        #[ink(constructor)]
+        pub fn new(lender: AccountId, repay: bool) -> Self {
+            Self { lender, repay }
+        }
+    }
+
+    impl FlashBorrower for MockFlashBorrower {
+        #[ink(message)]
+        fn on_flashloan(
+            &mut self,
+            _initiator: AccountId,
+            token: AccountId,
+            amount: Balance,
+            fee: Balance,
+            _data: Vec<u8>,
+        ) -> Result<(), FlashBorrowerError> {
+            if self.env().caller() != self.lender || token != self.lender {
+                return Err(FlashBorrowerError::FlashloanRejected(
+                    "Untrusted lender".to_string(),
+                ));
+            }
+
+            if self.repay {
+                PSP22Ref::approve_builder(&token, self.lender, amount + fee)
+                    .call_flags(CallFlags::default())
+                    .invoke()
+                    .map_err(|_| {
+                        FlashBorrowerError::FlashloanRejected("Approve failed".to_string())
+                    })?;
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::az_mock_price_oracle::MockPriceOracleRef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct PriceData {
+    pub price: u128,
+    pub timestamp: u64,
+}
+
+#[ink::trait_definition]
+pub trait PriceOracle {
+    #[ink(message)]
+    fn azero_usd_price(&self) -> PriceData;
+}
+
+// Minimal AZERO/USD price oracle for e2e tests. Whoever deploys it sets the
+// price and when it was last updated.
+#[ink::contract]
+pub mod az_mock_price_oracle {
+    use super::{PriceData, PriceOracle};
+
+    #[ink(storage)]
+    pub struct MockPriceOracle {
+        admin: AccountId,
+        price_data: PriceData,
+    }
+    impl MockPriceOracle {
+        #[ink(constructor)]
+        pub fn new(price: Balance, timestamp: Timestamp) -> Self {
+            Self {
+                admin: Self::env().caller(),
+                price_data: PriceData { price, timestamp },
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_price(&mut self, price: Balance, timestamp: Timestamp) {
+            assert_eq!(self.env().caller(), self.admin, "Unauthorised");
+            self.price_data = PriceData { price, timestamp };
+        }
+    }
+
+    impl PriceOracle for MockPriceOracle {
+        #[ink(message)]
+        fn azero_usd_price(&self) -> PriceData {
+            self.price_data
+        }
+    }
+}
@@ -0,0 +1,92 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::az_mock_router::MockRouterRef;
+
+use ink::primitives::AccountId;
+use openbrush::contracts::psp22::PSP22Error;
+
+type Balance = u128;
+type Timestamp = u64;
+
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum RouterError {
+    Expired,
+    InsufficientAmount,
+    PSP22Error(PSP22Error),
+}
+impl From<PSP22Error> for RouterError {
+    fn from(e: PSP22Error) -> Self {
+        RouterError::PSP22Error(e)
+    }
+}
+
+#[ink::trait_definition]
+pub trait Router {
+    #[ink(message, payable)]
+    fn add_liquidity_native(
+        &mut self,
+        token: AccountId,
+        amount_token_desired: Balance,
+        amount_token_min: Balance,
+        amount_native_min: Balance,
+        to: AccountId,
+        deadline: Timestamp,
+    ) -> Result<(Balance, Balance, Balance), RouterError>;
+}
+
+// Minimal router for e2e tests. It takes the whole token amount and AZERO sent,
+// and pays out one LP token per AZERO from its own LP token balance.
+#[ink::contract]
+pub mod az_mock_router {
+    use super::{Router, RouterError};
+    use ink::{env::CallFlags, prelude::vec};
+    use openbrush::contracts::psp22::PSP22Ref;
+
+    #[ink(storage)]
+    pub struct MockRouter {
+        lp_token: AccountId,
+    }
+    impl MockRouter {
+        #[ink(constructor)]
+        pub fn new(lp_token: AccountId) -> Self {
+            Self { lp_token }
+        }
+    }
+
+    impl Router for MockRouter {
+        #[ink(message, payable)]
+        fn add_liquidity_native(
+            &mut self,
+            token: AccountId,
+            amount_token_desired: Balance,
+            amount_token_min: Balance,
+            amount_native_min: Balance,
+            to: AccountId,
+            deadline: Timestamp,
+        ) -> Result<(Balance, Balance, Balance), RouterError> {
+            if deadline < self.env().block_timestamp() {
+                return Err(RouterError::Expired);
+            }
+            let amount_native: Balance = self.env().transferred_value();
+            if amount_token_desired < amount_token_min || amount_native < amount_native_min {
+                return Err(RouterError::InsufficientAmount);
+            }
+
+            PSP22Ref::transfer_from_builder(
+                &token,
+                self.env().caller(),
+                self.env().account_id(),
+                amount_token_desired,
+                vec![],
+            )
+            .call_flags(CallFlags::default())
+            .invoke()?;
+            PSP22Ref::transfer_builder(&self.lp_token, to, amount_native, vec![])
+                .call_flags(CallFlags::default())
+                .invoke()?;
+
+            Ok((amount_token_desired, amount_native, amount_native))
+        }
+    }
+}
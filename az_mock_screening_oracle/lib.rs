@@ -0,0 +1,57 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::az_mock_screening_oracle::MockScreeningOracleRef;
+
+use ink::primitives::AccountId;
+
+#[ink::trait_definition]
+pub trait ScreeningOracle {
+    #[ink(message)]
+    fn is_blocked(&self, account: AccountId) -> bool;
+}
+
+// Minimal screening oracle for e2e tests. Whoever deploys it decides which
+// accounts are blocked.
+#[ink::contract]
+pub mod az_mock_screening_oracle {
+    use super::ScreeningOracle;
+    use ink::storage::Mapping;
+
+    #[ink(storage)]
+    pub struct MockScreeningOracle {
+        admin: AccountId,
+        blocked: Mapping<AccountId, ()>,
+    }
+    impl MockScreeningOracle {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                admin: Self::env().caller(),
+                blocked: Mapping::default(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_blocked(&mut self, account: AccountId, blocked: bool) {
+            assert_eq!(self.env().caller(), self.admin, "Unauthorised");
+            if blocked {
+                self.blocked.insert(account, &());
+            } else {
+                self.blocked.remove(account);
+            }
+        }
+    }
+
+    impl Default for MockScreeningOracle {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ScreeningOracle for MockScreeningOracle {
+        #[ink(message)]
+        fn is_blocked(&self, account: AccountId) -> bool {
+            self.blocked.contains(account)
+        }
+    }
+}
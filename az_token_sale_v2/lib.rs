@@ -0,0 +1,130 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::az_token_sale_v2::{AZTokenSaleV2Ref, Config, Error, Sale};
+
+// Stand-in for the next release of AZTokenSale. The e2e suite upgrades a live
+// AZTokenSale to this code with set_code and then runs its migrate.
+#[ink::contract]
+pub mod az_token_sale_v2 {
+    use ink::{
+        primitives::KeyComposer,
+        storage::{traits::ManualKey, Lazy, Mapping},
+    };
+
+    // === CONSTANTS ===
+    const CONFIG_STORAGE_KEY: u32 = 0x434F_4E46;
+    // Where the current release keeps its sales
+    const SALES_STORAGE_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::sales");
+    const STORAGE_VERSION: u32 = 3;
+
+    // === TYPES ===
+    type Result<T> = core::result::Result<T, Error>;
+
+    // === ENUMS ===
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        NotFound,
+        Unauthorised,
+        UnprocessableEntity,
+    }
+
+    // === STRUCTS ===
+    #[derive(scale::Encode, scale::Decode)]
+    struct ConfigV2 {
+        version: u32,
+        admin: AccountId,
+    }
+
+    #[derive(Debug, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Config {
+        pub version: u32,
+        pub admin: AccountId,
+        pub migrated_at: BlockNumber,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Sale {
+        pub admin: AccountId,
+        pub out_token: AccountId,
+        pub in_unit: Balance,
+        pub out_unit: Balance,
+        pub inventory: Balance,
+        pub proceeds: Balance,
+        pub released: Balance,
+        pub sold: Balance,
+        pub start: Timestamp,
+        pub end: Option<Timestamp>,
+        pub mint_on_purchase: bool,
+    }
+
+    // === CONTRACT ===
+    #[ink(storage)]
+    pub struct AZTokenSaleV2 {
+        config: Lazy<Config, ManualKey<CONFIG_STORAGE_KEY>>,
+        sales: Mapping<u32, Sale, ManualKey<SALES_STORAGE_KEY>>,
+    }
+    impl Default for AZTokenSaleV2 {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl AZTokenSaleV2 {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            let mut config = Lazy::new();
+            config.set(&Config {
+                version: STORAGE_VERSION,
+                admin: Self::env().caller(),
+                migrated_at: Self::env().block_number(),
+            });
+            Self {
+                config,
+                sales: Mapping::default(),
+            }
+        }
+
+        // === QUERIES ===
+        #[ink(message)]
+        pub fn config(&self) -> Config {
+            self.config.get().unwrap()
+        }
+
+        #[ink(message)]
+        pub fn sale(&self, sale_id: u32) -> Option<Sale> {
+            self.sales.get(sale_id)
+        }
+
+        // === HANDLES ===
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<u32> {
+            let old: ConfigV2 =
+                match ink::env::get_contract_storage::<u32, ConfigV2>(&CONFIG_STORAGE_KEY) {
+                    Ok(Some(old)) => old,
+                    _ => return Err(Error::NotFound),
+                };
+            if old.admin != Self::env().caller() {
+                return Err(Error::Unauthorised);
+            }
+            if old.version != 2 {
+                return Err(Error::UnprocessableEntity);
+            }
+
+            self.config.set(&Config {
+                version: STORAGE_VERSION,
+                admin: old.admin,
+                migrated_at: Self::env().block_number(),
+            });
+
+            Ok(STORAGE_VERSION)
+        }
+    }
+}
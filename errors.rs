@@ -1,3 +1,4 @@
+use crate::{az_token_sale::SaleStatus, traits::RouterError};
 use ink::{
     env::Error as InkEnvError,
     prelude::{format, string::String},
@@ -10,7 +11,12 @@ use openbrush::contracts::psp22::PSP22Error;
 pub enum AZTokenSaleError {
     ContractCall(LangError),
     InkEnvError(String),
+    InvalidSaleStatus(SaleStatus),
+    // (from, to)
+    InvalidTransition(SaleStatus, SaleStatus),
+    NotFound(String),
     PSP22Error(PSP22Error),
+    RouterError(RouterError),
     Unauthorised,
     UnprocessableEntity(String),
 }
@@ -29,3 +35,8 @@ impl From<PSP22Error> for AZTokenSaleError {
         AZTokenSaleError::PSP22Error(e)
     }
 }
+impl From<RouterError> for AZTokenSaleError {
+    fn from(e: RouterError) -> Self {
+        AZTokenSaleError::RouterError(e)
+    }
+}
@@ -1,21 +1,275 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
 mod errors;
+mod pricing;
+mod traits;
 
 #[ink::contract]
 mod az_token_sale {
-    use crate::errors::AZTokenSaleError;
-    use ink::{env::CallFlags, prelude::string::ToString, prelude::vec};
-    use openbrush::contracts::psp22::PSP22Ref;
+    use crate::{
+        errors::AZTokenSaleError,
+        pricing::{Fill, Price},
+        traits::{PermitRef, PriceData, PriceOracleRef, RouterRef, ScreeningOracleRef},
+    };
+    use ink::{
+        env::{hash::Blake2x256, CallFlags},
+        prelude::string::{String, ToString},
+        prelude::{vec, vec::Vec},
+        primitives::KeyComposer,
+        storage::{
+            traits::{ManualKey, Storable},
+            Lazy, Mapping,
+        },
+    };
+    use openbrush::contracts::psp22::{extensions::mintable::PSP22MintableRef, PSP22Ref};
     use primitive_types::U256;
 
+    // === CONSTANTS ===
+    // Config lives under a fixed key so that upgraded code can always find it.
+    const CONFIG_STORAGE_KEY: u32 = 0x434F_4E46;
+    // Bump whenever the encoding of anything already in storage changes,
+    // and teach `migrate` how to get from the previous version.
+    const STORAGE_VERSION: u32 = 2;
+    const MAX_BONUS_BRACKETS: usize = 10;
+    const MAX_TRANCHES: usize = 10;
+    // Where v1 kept the settings of its single sale.
+    const V1_ANTI_BOT_CONFIG_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::anti_bot_config");
+    const V1_BLOCK_OUT_AMOUNT_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::block_out_amount");
+    const V1_BONUS_BRACKETS_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::bonus_brackets");
+    const V1_KYC_SIGNER_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::kyc_signer");
+    const V1_LIQUIDITY_CONFIG_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::liquidity_config");
+    const V1_LIQUIDITY_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::liquidity");
+    const V1_MINT_ON_PURCHASE_KEY: u32 = KeyComposer::from_bytes(b"AZTokenSale::mint_on_purchase");
+
     // === TYPES ===
     type Result<T> = core::result::Result<T, AZTokenSaleError>;
+    // Most AZERO an allowlisted account can spend during the allowlist phase
+    pub type Allocation = Balance;
+    // (sale id, buyer, nonce)
+    type VoucherNonce = (u32, AccountId, u64);
+    // (sale id, tranche index, voter)
+    type TrancheVoter = (u32, u32, AccountId);
+
+    // === ENUMS ===
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum SaleStatus {
+        // Before the window opens
+        Pending,
+        Active,
+        Paused,
+        // After the window closes
+        Ended,
+        Finalized,
+        Cancelled,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum TrancheUnlock {
+        Timestamp(Timestamp),
+        // Once buyers holding more than half of the out_token sold vote for it
+        Vote,
+    }
 
     // === STRUCTS ===
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct BonusBracket {
+        pub min_in_amount: Balance,
+        // Extra out_token on top of the base amount
+        pub bonus_percentage: u8,
+    }
+
     #[derive(Debug, Clone, scale::Encode, scale::Decode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
     pub struct Config {
+        // version and admin must stay the first two fields in every layout
+        // so that `migrate` can read them without decoding the rest.
+        version: u32,
+        admin: AccountId,
+        // Reports the USD value of AZERO for sales priced in USD
+        price_oracle: Option<AccountId>,
+        // Oldest oracle price, in milliseconds, that buy will accept
+        max_price_age: Timestamp,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Sale {
+        pub admin: AccountId,
+        pub out_token: AccountId,
+        pub in_unit: Balance,
+        pub out_unit: Balance,
+        // out_token added for this sale and not yet sold
+        pub inventory: Balance,
+        // AZERO paid for this sale and still held in escrow, apart from any
+        // held back for liquidity
+        pub proceeds: Balance,
+        // AZERO released from escrow to the admin
+        pub released: Balance,
+        // out_token received by buyers, which weighs votes on tranches
+        pub sold: Balance,
+        pub start: Timestamp,
+        pub end: Option<Timestamp>,
+        // Mint out_token to buyers instead of selling from inventory.
+        // Requires the contract to be a minter on out_token.
+        pub mint_on_purchase: bool,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct AntiBotConfig {
+        // Minimum number of blocks between purchases by the same account. 0 disables.
+        pub cooldown_blocks: BlockNumber,
+        pub max_out_amount_per_block: Option<Balance>,
+        pub no_contract_callers: bool,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Contribution {
+        // AZERO accepted from the buyer
+        pub in_amount: Balance,
+        // out_token the buyer received, which they return for a refund
+        pub out_amount: Balance,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Liquidity {
+        // AZERO held back from purchases for liquidity
+        pub azero_amount: Balance,
+        // LP tokens locked in the contract by finalize
+        pub lp_amount: Balance,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct LiquidityConfig {
+        pub router: AccountId,
+        pub lp_token: AccountId,
+        // Share of each purchase's AZERO held back for liquidity
+        pub azero_percentage: u8,
+        pub lp_unlock_timestamp: Timestamp,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Tranche {
+        // Share of the escrowed proceeds at finalization
+        pub percentage: u8,
+        pub unlock: TrancheUnlock,
+    }
+
+    // What a purchase would do right now, as worked out by buy itself
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Quote {
+        // AZERO the sale would keep
+        pub accepted_in: Balance,
+        // out_token the sale would send, before any fee out_token takes on transfer
+        pub out_amount: Balance,
+        // AZERO that would go back to the buyer, including dust
+        pub refund: Balance,
+        // Least AZERO that still buys out_token afterwards. None once sold out.
+        pub price_after: Option<Balance>,
+    }
+
+    // Project info for launchpads. The hashes are of files kept off-chain.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Metadata {
+        pub name: String,
+        pub description_uri: String,
+        pub website: String,
+        pub logo_hash: Option<Hash>,
+        // Buyers must pass this hash to buy to show they agreed to the terms
+        pub terms_hash: Option<Hash>,
+    }
+
+    // Sale price in USD, in the units the price oracle uses. Replaces
+    // in_unit and out_unit while set.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct UsdPrice {
+        pub usd_amount: Balance,
+        pub out_amount: Balance,
+    }
+
+    // Off-chain approval for a single purchase, signed by the sale's KYC signer
+    // over blake2x256((contract address, sale id, voucher)).
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Voucher {
+        pub buyer: AccountId,
+        pub max_allocation: Balance,
+        pub expiry: Timestamp,
+        pub nonce: u64,
+    }
+
+    struct BuyPlan {
+        sale: Sale,
+        anti_bot_config: AntiBotConfig,
+        block_number: BlockNumber,
+        // out amount already sold this block
+        block_out_amount: Balance,
+        fill: Fill,
+        price_after: Option<Balance>,
+    }
+
+    #[derive(scale::Encode, scale::Decode)]
+    struct StorageHeader {
+        version: u32,
+        admin: AccountId,
+    }
+
+    #[derive(scale::Encode, scale::Decode)]
+    struct LiquidityV1 {
+        azero_amount: Balance,
+        lp_amount: Balance,
+        finalized: bool,
+    }
+
+    #[derive(scale::Encode, scale::Decode)]
+    struct ConfigV1 {
+        version: u32,
         admin: AccountId,
         out_token: AccountId,
         in_unit: Balance,
@@ -25,245 +279,5378 @@ mod az_token_sale {
     // === CONTRACT ===
     #[ink(storage)]
     pub struct AZTokenSale {
-        admin: AccountId,
-        out_token: AccountId,
-        in_unit: Balance,
-        out_unit: Balance,
+        config: Lazy<Config, ManualKey<CONFIG_STORAGE_KEY>>,
+        sales: Mapping<u32, Sale>,
+        sales_count: Lazy<u32>,
+        // Ethereum address of the ECDSA key that signs a sale's vouchers.
+        // When set, purchases require a voucher.
+        kyc_signer: Mapping<u32, [u8; 20]>,
+        used_voucher_nonces: Mapping<VoucherNonce, ()>,
+        anti_bot_config: Mapping<u32, AntiBotConfig>,
+        last_purchase_blocks: Mapping<(u32, AccountId), BlockNumber>,
+        // (block number, out amount sold in that block)
+        block_out_amount: Mapping<u32, (BlockNumber, Balance)>,
+        liquidity: Mapping<u32, Liquidity>,
+        liquidity_config: Mapping<u32, LiquidityConfig>,
+        // Ordered by min_in_amount, ascending
+        bonus_brackets: Mapping<u32, Vec<BonusBracket>>,
+        // Set by pause, finalize and cancel. Otherwise a sale is Pending,
+        // Active or Ended according to its window.
+        status: Mapping<u32, SaleStatus>,
+        contributions: Mapping<(u32, AccountId), Contribution>,
+        // Can cancel any sale, alongside each sale's admin
+        guardian: Lazy<Option<AccountId>>,
+        // Releases of a finalized sale's proceeds. Without tranches they are
+        // released all at once.
+        tranches: Mapping<u32, Vec<Tranche>>,
+        // AZERO released by each tranche
+        released_tranches: Mapping<(u32, u32), Balance>,
+        tranche_votes: Mapping<(u32, u32), Balance>,
+        tranche_voters: Mapping<TrancheVoter, ()>,
+        allowlist: Mapping<(u32, AccountId), Allocation>,
+        // Present while a sale is allowlist-only. It goes public at the
+        // timestamp, or when its admin ends the phase if there is none.
+        allowlist_phase: Mapping<u32, Option<Timestamp>>,
+        // Accounts that can not buy from any sale
+        blocklist: Mapping<AccountId, ()>,
+        // Asked about every buyer when set
+        screening_oracle: Lazy<Option<AccountId>>,
+        usd_price: Mapping<u32, UsdPrice>,
+        metadata: Mapping<u32, Metadata>,
     }
     impl AZTokenSale {
         #[ink(constructor)]
-        pub fn new(out_token: AccountId, in_unit: Balance, out_unit: Balance) -> Self {
-            Self {
+        pub fn new() -> Self {
+            let mut config = Lazy::new();
+            config.set(&Config {
+                version: STORAGE_VERSION,
                 admin: Self::env().caller(),
-                out_token,
-                in_unit,
-                out_unit,
+                price_oracle: None,
+                max_price_age: 0,
+            });
+            Self {
+                config,
+                sales: Mapping::default(),
+                sales_count: Lazy::new(),
+                kyc_signer: Mapping::default(),
+                used_voucher_nonces: Mapping::default(),
+                anti_bot_config: Mapping::default(),
+                last_purchase_blocks: Mapping::default(),
+                block_out_amount: Mapping::default(),
+                liquidity: Mapping::default(),
+                liquidity_config: Mapping::default(),
+                bonus_brackets: Mapping::default(),
+                status: Mapping::default(),
+                contributions: Mapping::default(),
+                guardian: Lazy::new(),
+                tranches: Mapping::default(),
+                released_tranches: Mapping::default(),
+                tranche_votes: Mapping::default(),
+                tranche_voters: Mapping::default(),
+                allowlist: Mapping::default(),
+                allowlist_phase: Mapping::default(),
+                blocklist: Mapping::default(),
+                screening_oracle: Lazy::new(),
+                usd_price: Mapping::default(),
+                metadata: Mapping::default(),
             }
         }
 
         // === QUERIES ===
         #[ink(message)]
-        pub fn config(&self) -> Config {
-            Config {
-                admin: self.admin,
-                out_token: self.out_token,
-                in_unit: self.in_unit,
-                out_unit: self.out_unit,
+        pub fn allocation(&self, sale_id: u32, account: AccountId) -> Option<Allocation> {
+            self.allowlist.get((sale_id, account))
+        }
+
+        #[ink(message)]
+        pub fn allowlist_only(&self, sale_id: u32) -> bool {
+            match self.allowlist_phase.get(sale_id) {
+                Some(Some(public_at)) => Self::env().block_timestamp() < public_at,
+                Some(None) => true,
+                None => false,
             }
         }
 
+        #[ink(message)]
+        pub fn allowlist_public_at(&self, sale_id: u32) -> Option<Timestamp> {
+            self.allowlist_phase.get(sale_id).flatten()
+        }
+
+        #[ink(message)]
+        pub fn anti_bot_config(&self, sale_id: u32) -> AntiBotConfig {
+            self.anti_bot_config.get(sale_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn blocklisted(&self, account: AccountId) -> bool {
+            self.blocklist.contains(account)
+        }
+
+        #[ink(message)]
+        pub fn bonus_brackets(&self, sale_id: u32) -> Vec<BonusBracket> {
+            self.bonus_brackets.get(sale_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn config(&self) -> Config {
+            self.config.get().unwrap()
+        }
+
+        #[ink(message)]
+        pub fn contribution(&self, sale_id: u32, buyer: AccountId) -> Contribution {
+            self.contributions.get((sale_id, buyer)).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn guardian(&self) -> Option<AccountId> {
+            self.guardian.get().flatten()
+        }
+
+        #[ink(message)]
+        pub fn kyc_signer(&self, sale_id: u32) -> Option<[u8; 20]> {
+            self.kyc_signer.get(sale_id)
+        }
+
+        #[ink(message)]
+        pub fn liquidity(&self, sale_id: u32) -> Liquidity {
+            self.liquidity.get(sale_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn liquidity_config(&self, sale_id: u32) -> Option<LiquidityConfig> {
+            self.liquidity_config.get(sale_id)
+        }
+
+        // AZERO still held in escrow for a sale
+        #[ink(message)]
+        pub fn locked(&self, sale_id: u32) -> Result<Balance> {
+            Ok(self.sale(sale_id)?.proceeds)
+        }
+
+        #[ink(message)]
+        pub fn metadata(&self, sale_id: u32) -> Option<Metadata> {
+            self.metadata.get(sale_id)
+        }
+
+        // Previews buy for the caller. Vouchers are not checked.
+        #[ink(message)]
+        pub fn quote(
+            &self,
+            sale_id: u32,
+            in_amount: Balance,
+            terms_hash: Option<Hash>,
+        ) -> Result<Quote> {
+            let buy_plan: BuyPlan =
+                self.plan_buy(sale_id, Self::env().caller(), in_amount, terms_hash)?;
+
+            Ok(Quote {
+                accepted_in: buy_plan.fill.accepted_in,
+                out_amount: buy_plan.fill.out_amount,
+                refund: buy_plan.fill.refund,
+                price_after: buy_plan.price_after,
+            })
+        }
+
+        // AZERO released from escrow to a sale's admin
+        #[ink(message)]
+        pub fn released(&self, sale_id: u32) -> Result<Balance> {
+            Ok(self.sale(sale_id)?.released)
+        }
+
+        #[ink(message)]
+        pub fn sale(&self, sale_id: u32) -> Result<Sale> {
+            self.sales
+                .get(sale_id)
+                .ok_or(AZTokenSaleError::NotFound("Sale".to_string()))
+        }
+
+        #[ink(message)]
+        pub fn sales_count(&self) -> u32 {
+            self.sales_count.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn screening_oracle(&self) -> Option<AccountId> {
+            self.screening_oracle.get().flatten()
+        }
+
+        #[ink(message)]
+        pub fn status(&self, sale_id: u32) -> Result<SaleStatus> {
+            let sale: Sale = self.sale(sale_id)?;
+
+            Ok(self.sale_status(sale_id, &sale))
+        }
+
+        #[ink(message)]
+        pub fn tranche_released(&self, sale_id: u32, index: u32) -> Option<Balance> {
+            self.released_tranches.get((sale_id, index))
+        }
+
+        #[ink(message)]
+        pub fn tranche_votes(&self, sale_id: u32, index: u32) -> Balance {
+            self.tranche_votes.get((sale_id, index)).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn tranches(&self, sale_id: u32) -> Vec<Tranche> {
+            self.tranches.get(sale_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn usd_price(&self, sale_id: u32) -> Option<UsdPrice> {
+            self.usd_price.get(sale_id)
+        }
+
+        #[ink(message)]
+        pub fn voucher_nonce_used(&self, sale_id: u32, buyer: AccountId, nonce: u64) -> bool {
+            self.used_voucher_nonces.contains((sale_id, buyer, nonce))
+        }
+
         // === HANDLES ===
+        // Returns the amount received, which is less than amount for
+        // fee-on-transfer tokens.
         #[ink(message)]
-        pub fn add_amount_for_sale(&mut self, amount: Balance) -> Result<()> {
+        pub fn add_amount_for_sale(&mut self, sale_id: u32, amount: Balance) -> Result<Balance> {
             let caller: AccountId = Self::env().caller();
-            Self::authorise(self.admin, caller)?;
-            // validate in amount is in units of in_unit
-            if amount == 0 || amount % self.out_unit > 0 {
-                return Err(AZTokenSaleError::UnprocessableEntity(
-                    "Amount must be in multiples of out_unit".to_string(),
-                ));
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, caller)?;
+
+            self.add_inventory(sale_id, sale, caller, amount)
+        }
+
+        // Approves this contract via the token's permit and adds the amount for
+        // sale in one transaction. The tokens come from the permit's owner, who
+        // does not have to be the admin.
+        #[ink(message)]
+        pub fn add_amount_for_sale_with_permit(
+            &mut self,
+            sale_id: u32,
+            owner: AccountId,
+            amount: Balance,
+            deadline: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<Balance> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+
+            PermitRef::permit_builder(
+                &sale.out_token,
+                owner,
+                self.env().account_id(),
+                amount,
+                deadline,
+                signature,
+            )
+            .call_flags(CallFlags::default())
+            .invoke()?;
+
+            self.add_inventory(sale_id, sale, owner, amount)
+        }
+
+        // Adds or updates allocations.
+        #[ink(message)]
+        pub fn add_to_allowlist(
+            &mut self,
+            sale_id: u32,
+            allocations: Vec<(AccountId, Allocation)>,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            for (account, allocation) in allocations {
+                self.allowlist.insert((sale_id, account), &allocation);
             }
 
-            self.acquire_psp22(self.out_token, caller, amount)?;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_to_blocklist(&mut self, accounts: Vec<AccountId>) -> Result<()> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            for account in accounts {
+                self.blocklist.insert(account, &());
+            }
 
             Ok(())
         }
 
         #[ink(message, payable)]
-        pub fn buy(&mut self) -> Result<(Balance, Balance)> {
+        pub fn buy(
+            &mut self,
+            sale_id: u32,
+            terms_hash: Option<Hash>,
+        ) -> Result<(Balance, Balance)> {
+            if self.kyc_signer(sale_id).is_some() {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Voucher required".to_string(),
+                ));
+            }
+
+            self.process_buy(
+                sale_id,
+                Self::env().caller(),
+                self.env().transferred_value(),
+                terms_hash,
+            )
+        }
+
+        #[ink(message, payable)]
+        pub fn buy_with_voucher(
+            &mut self,
+            sale_id: u32,
+            voucher: Voucher,
+            signature: [u8; 65],
+            terms_hash: Option<Hash>,
+        ) -> Result<(Balance, Balance)> {
             let caller: AccountId = Self::env().caller();
-            // validate in amount is in units of in_unit
-            let mut in_amount: Balance = self.env().transferred_value();
-            if in_amount == 0 || in_amount % self.in_unit > 0 {
+            let in_amount: Balance = self.env().transferred_value();
+            let kyc_signer: [u8; 20] =
+                self.kyc_signer(sale_id)
+                    .ok_or(AZTokenSaleError::UnprocessableEntity(
+                        "Vouchers are not enabled".to_string(),
+                    ))?;
+            // validate voucher
+            if voucher.buyer != caller {
+                return Err(AZTokenSaleError::Unauthorised);
+            }
+            if voucher.expiry < Self::env().block_timestamp() {
                 return Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string(),
+                    "Voucher has expired".to_string(),
                 ));
             }
-            // validate balance is positive
-            let contract_address: AccountId = Self::env().account_id();
-            let contract_balance: Balance = PSP22Ref::balance_of(&self.out_token, contract_address);
-            if contract_balance == 0 {
+            if self.voucher_nonce_used(sale_id, voucher.buyer, voucher.nonce) {
                 return Err(AZTokenSaleError::UnprocessableEntity(
-                    "Sold out".to_string(),
+                    "Voucher has already been used".to_string(),
                 ));
             }
-
-            // Calculate max in amount for refund
-            let desired_out_amount: Balance = in_amount * self.out_unit / self.in_unit;
-            let max_in_amount: Balance = if contract_balance >= desired_out_amount {
-                in_amount
-            } else {
-                (U256::from(in_amount) * U256::from(contract_balance)
-                    / U256::from(desired_out_amount))
-                .as_u128()
-            };
-
-            // refund if necessary
-            if in_amount > max_in_amount {
-                let refund_amount: Balance = in_amount - max_in_amount;
-                self.transfer_azero(caller, refund_amount)?;
-                in_amount = max_in_amount
+            if in_amount > voucher.max_allocation {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount exceeds voucher allocation".to_string(),
+                ));
+            }
+            // validate signature
+            let message_hash: [u8; 32] = self.env().hash_encoded::<Blake2x256, _>(&(
+                Self::env().account_id(),
+                sale_id,
+                &voucher,
+            ));
+            let signer: Option<[u8; 20]> = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .and_then(|public_key| self.env().ecdsa_to_eth_address(&public_key))
+                .ok();
+            if signer != Some(kyc_signer) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Invalid signature".to_string(),
+                ));
             }
 
-            // Trasfer out token to user
-            let out_amount: Balance = (U256::from(in_amount) * U256::from(self.out_unit)
-                / U256::from(self.in_unit))
-            .as_u128();
-            PSP22Ref::transfer_builder(&self.out_token, caller, out_amount, vec![])
-                .call_flags(CallFlags::default())
-                .invoke()?;
-
-            // Send AZERO to admin
-            self.transfer_azero(self.admin, in_amount)?;
-
-            Ok((in_amount, out_amount))
+            self.used_voucher_nonces
+                .insert((sale_id, voucher.buyer, voucher.nonce), &());
+            self.process_buy(sale_id, caller, in_amount, terms_hash)
         }
 
-        // === PRIVATE ===
-        fn authorise(allowed: AccountId, received: AccountId) -> Result<()> {
-            if allowed != received {
-                return Err(AZTokenSaleError::Unauthorised);
+        // Stops the sale for good and lets buyers claim refunds.
+        #[ink(message)]
+        pub fn cancel(&mut self, sale_id: u32) -> Result<()> {
+            let mut sale: Sale = self.sale(sale_id)?;
+            let caller: AccountId = Self::env().caller();
+            if self.guardian() != Some(caller) {
+                Self::authorise(sale.admin, caller)?;
+            }
+            self.transition(sale_id, &sale, SaleStatus::Cancelled)?;
+
+            // Refunds come out of escrow, including AZERO held back for liquidity
+            let mut liquidity: Liquidity = self.liquidity(sale_id);
+            if liquidity.azero_amount > 0 {
+                sale.proceeds += liquidity.azero_amount;
+                self.sales.insert(sale_id, &sale);
+                liquidity.azero_amount = 0;
+                self.liquidity.insert(sale_id, &liquidity);
             }
 
             Ok(())
         }
 
-        fn acquire_psp22(&self, token: AccountId, from: AccountId, amount: Balance) -> Result<()> {
-            PSP22Ref::transfer_from_builder(&token, from, self.env().account_id(), amount, vec![])
-                .call_flags(CallFlags::default())
-                .invoke()?;
+        #[ink(message)]
+        pub fn create_sale(
+            &mut self,
+            admin: AccountId,
+            out_token: AccountId,
+            in_unit: Balance,
+            out_unit: Balance,
+            start: Timestamp,
+            end: Option<Timestamp>,
+        ) -> Result<u32> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            if in_unit == 0 || out_unit == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Units must be positive".to_string(),
+                ));
+            }
+            if let Some(end) = end {
+                if end <= start {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "End must be after start".to_string(),
+                    ));
+                }
+            }
 
-            Ok(())
+            let sale_id: u32 = self.sales_count();
+            self.sales.insert(
+                sale_id,
+                &Sale {
+                    admin,
+                    out_token,
+                    in_unit,
+                    out_unit,
+                    inventory: 0,
+                    proceeds: 0,
+                    released: 0,
+                    sold: 0,
+                    start,
+                    end,
+                    mint_on_purchase: false,
+                },
+            );
+            self.sales_count.set(&(sale_id + 1));
+
+            Ok(sale_id)
         }
 
-        fn transfer_azero(&self, address: AccountId, amount: Balance) -> Result<()> {
-            if self.env().transfer(address, amount).is_err() {
+        // Opens an allowlist-only sale to the public.
+        #[ink(message)]
+        pub fn end_allowlist_phase(&mut self, sale_id: u32) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            if !self.allowlist_only(sale_id) {
                 return Err(AZTokenSaleError::UnprocessableEntity(
-                    "Insufficient AZERO balance".to_string(),
+                    "Sale is not allowlist-only".to_string(),
                 ));
             }
+            self.allowlist_phase.remove(sale_id);
 
             Ok(())
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::{
-            test::{default_accounts, set_caller, DefaultAccounts},
-            DefaultEnvironment,
-        };
+        // Ends the sale. When liquidity is configured, pairs the AZERO held back
+        // for it with out_token at the sale price and adds both to the router.
+        #[ink(message)]
+        pub fn finalize(&mut self, sale_id: u32) -> Result<Liquidity> {
+            let mut sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.transition(sale_id, &sale, SaleStatus::Finalized)?;
 
-        // === CONSTANTS ===
-        const MOCK_IN_UNIT: Balance = 250;
-        const MOCK_OUT_UNIT: Balance = 1;
+            let mut liquidity: Liquidity = self.liquidity(sale_id);
+            if let Some(liquidity_config) = self
+                .liquidity_config(sale_id)
+                .filter(|_| liquidity.azero_amount > 0)
+            {
+                let contract_address: AccountId = Self::env().account_id();
+                let out_amount: Balance = self
+                    .price(sale_id, &sale, &[])?
+                    .base_out_amount(liquidity.azero_amount);
+                if sale.mint_on_purchase {
+                    PSP22MintableRef::mint_builder(&sale.out_token, contract_address, out_amount)
+                        .call_flags(CallFlags::default())
+                        .invoke()?;
+                } else if sale.inventory < out_amount {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "Insufficient out_token for liquidity".to_string(),
+                    ));
+                } else {
+                    sale.inventory -= out_amount;
+                    self.sales.insert(sale_id, &sale);
+                }
 
-        // === HELPERS ===
-        fn init() -> (DefaultAccounts<DefaultEnvironment>, AZTokenSale) {
-            let accounts = default_accounts();
-            set_caller::<DefaultEnvironment>(accounts.alice);
-            let token_sale = AZTokenSale::new(accounts.eve, MOCK_IN_UNIT, MOCK_OUT_UNIT);
-            (accounts, token_sale)
+                PSP22Ref::approve_builder(&sale.out_token, liquidity_config.router, out_amount)
+                    .call_flags(CallFlags::default())
+                    .invoke()?;
+                let (_, _, lp_amount) = RouterRef::add_liquidity_native_builder(
+                    &liquidity_config.router,
+                    sale.out_token,
+                    out_amount,
+                    out_amount,
+                    liquidity.azero_amount,
+                    contract_address,
+                    Self::env().block_timestamp(),
+                )
+                .transferred_value(liquidity.azero_amount)
+                .call_flags(CallFlags::default())
+                .invoke()?;
+                liquidity.azero_amount = 0;
+                liquidity.lp_amount += lp_amount;
+                self.liquidity.insert(sale_id, &liquidity);
+            }
+
+            Ok(liquidity)
         }
 
-        // === TESTS ===
-        // === TEST QUERIES ===
-        #[ink::test]
-        fn test_config() {
-            let (accounts, token_sale) = init();
-            let config = token_sale.config();
-            // * it returns the config
-            assert_eq!(config.admin, accounts.alice);
-            assert_eq!(config.out_token, token_sale.out_token);
-            assert_eq!(config.in_unit, token_sale.in_unit);
-            assert_eq!(config.out_unit, token_sale.out_unit);
+        #[ink(message)]
+        pub fn invalidate_voucher_nonce(
+            &mut self,
+            sale_id: u32,
+            buyer: AccountId,
+            nonce: u64,
+        ) -> Result<()> {
+            Self::authorise(self.sale(sale_id)?.admin, Self::env().caller())?;
+            self.used_voucher_nonces
+                .insert((sale_id, buyer, nonce), &());
+
+            Ok(())
         }
 
-        #[ink::test]
-        fn test_buy() {
-            let (_accounts, mut az_token_sale) = init();
+        // Run straight after set_code so that storage matches the new code.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<u32> {
+            let header: StorageHeader =
+                ink::env::get_contract_storage::<u32, StorageHeader>(&CONFIG_STORAGE_KEY)?
+                    .ok_or(AZTokenSaleError::NotFound("Config".to_string()))?;
+            Self::authorise(header.admin, Self::env().caller())?;
 
-            // when in amount is zero
-            // * it raises an error
-            let mut result = az_token_sale.buy();
-            assert_eq!(
-                result,
-                Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string()
-                ))
-            );
-            // when in amount is positive
-            // = when in amount is not a multiple of in_unit
-            // = * it raises an error
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(MOCK_IN_UNIT + 1);
-            result = az_token_sale.buy();
-            assert_eq!(
-                result,
-                Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string(),
+            match header.version {
+                1 => {
+                    self.migrate_from_v1()?;
+                    Ok(STORAGE_VERSION)
+                }
+                STORAGE_VERSION => Err(AZTokenSaleError::UnprocessableEntity(
+                    "Storage is already up to date".to_string(),
                 )),
-            );
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(MOCK_IN_UNIT - 1);
-            result = az_token_sale.buy();
-            assert_eq!(
-                result,
-                Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string()
-                ))
-            );
-            // = when in amount is a multiple of in_unit
-            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS AIRDROP SMART CONTRACT
+                _ => Err(AZTokenSaleError::UnprocessableEntity(
+                    "Unknown storage version".to_string(),
+                )),
+            }
         }
-    }
 
-    #[cfg(all(test, feature = "e2e-tests"))]
-    mod e2e_tests {
-        use super::*;
-        use crate::az_token_sale::AZTokenSaleRef;
-        use az_button::ButtonRef;
-        use ink_e2e::build_message;
-        use ink_e2e::Keypair;
-        use openbrush::contracts::traits::psp22::psp22_external::PSP22;
+        #[ink(message)]
+        pub fn pause(&mut self, sale_id: u32) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.transition(sale_id, &sale, SaleStatus::Paused)
+        }
 
-        // === CONSTANTS ===
-        const MOCK_IN_UNIT: Balance = 250;
+        // Returns a buyer's AZERO from a cancelled sale in exchange for the
+        // out_token they received, which they must have approved.
+        #[ink(message)]
+        pub fn refund(&mut self, sale_id: u32) -> Result<Balance> {
+            let caller: AccountId = Self::env().caller();
+            let mut sale: Sale = self.sale(sale_id)?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Cancelled {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            let contribution: Contribution = self.contribution(sale_id, caller);
+            if contribution.in_amount == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Nothing to refund".to_string(),
+                ));
+            }
+
+            self.contributions.remove((sale_id, caller));
+            if contribution.out_amount > 0 {
+                sale.inventory +=
+                    self.acquire_psp22(sale.out_token, caller, contribution.out_amount)?;
+            }
+            sale.proceeds -= contribution.in_amount;
+            sale.sold -= contribution.out_amount;
+            self.sales.insert(sale_id, &sale);
+            self.transfer_azero(caller, contribution.in_amount)?;
+
+            Ok(contribution.in_amount)
+        }
+
+        #[ink(message)]
+        pub fn release_lp_tokens(&mut self, sale_id: u32) -> Result<Balance> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            let liquidity_config: LiquidityConfig =
+                self.liquidity_config(sale_id)
+                    .ok_or(AZTokenSaleError::UnprocessableEntity(
+                        "Liquidity is not configured".to_string(),
+                    ))?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Finalized {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            let mut liquidity: Liquidity = self.liquidity(sale_id);
+            if Self::env().block_timestamp() < liquidity_config.lp_unlock_timestamp {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "LP tokens are locked".to_string(),
+                ));
+            }
+            if liquidity.lp_amount == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "No LP tokens to release".to_string(),
+                ));
+            }
+
+            let lp_amount: Balance = liquidity.lp_amount;
+            liquidity.lp_amount = 0;
+            self.liquidity.insert(sale_id, &liquidity);
+            PSP22Ref::transfer_builder(&liquidity_config.lp_token, sale.admin, lp_amount, vec![])
+                .call_flags(CallFlags::default())
+                .invoke()?;
+
+            Ok(lp_amount)
+        }
+
+        // Sends a finalized sale's admin their share of the escrowed proceeds
+        // once the tranche has unlocked.
+        #[ink(message)]
+        pub fn release_tranche(&mut self, sale_id: u32, index: u32) -> Result<Balance> {
+            let mut sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Finalized {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            let tranches: Vec<Tranche> = self.sale_tranches(sale_id);
+            let tranche: &Tranche = tranches
+                .get(index as usize)
+                .ok_or(AZTokenSaleError::NotFound("Tranche".to_string()))?;
+            if self.released_tranches.contains((sale_id, index)) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche already released".to_string(),
+                ));
+            }
+            let unlocked: bool = match tranche.unlock {
+                TrancheUnlock::Timestamp(timestamp) => Self::env().block_timestamp() >= timestamp,
+                TrancheUnlock::Vote => {
+                    U256::from(self.tranche_votes(sale_id, index)) * U256::from(2)
+                        > U256::from(sale.sold)
+                }
+            };
+            if !unlocked {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche is locked".to_string(),
+                ));
+            }
+
+            // The last tranche takes whatever rounding has left in escrow
+            let last: bool = (0..tranches.len() as u32)
+                .filter(|i| *i != index)
+                .all(|i| self.released_tranches.contains((sale_id, i)));
+            let amount: Balance = if last {
+                sale.proceeds
+            } else {
+                (U256::from(sale.proceeds + sale.released) * U256::from(tranche.percentage)
+                    / U256::from(100))
+                .as_u128()
+                .min(sale.proceeds)
+            };
+            self.released_tranches.insert((sale_id, index), &amount);
+            sale.proceeds -= amount;
+            sale.released += amount;
+            self.sales.insert(sale_id, &sale);
+            if amount > 0 {
+                self.transfer_azero(sale.admin, amount)?;
+            }
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        pub fn remove_from_allowlist(
+            &mut self,
+            sale_id: u32,
+            accounts: Vec<AccountId>,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            for account in accounts {
+                self.allowlist.remove((sale_id, account));
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_from_blocklist(&mut self, accounts: Vec<AccountId>) -> Result<()> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            for account in accounts {
+                self.blocklist.remove(account);
+            }
+
+            Ok(())
+        }
+
+        // Makes the sale allowlist-only until public_at, or until the admin
+        // calls end_allowlist_phase if there is none.
+        #[ink(message)]
+        pub fn set_allowlist_phase(
+            &mut self,
+            sale_id: u32,
+            public_at: Option<Timestamp>,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            self.allowlist_phase.insert(sale_id, &public_at);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_anti_bot_config(
+            &mut self,
+            sale_id: u32,
+            anti_bot_config: AntiBotConfig,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            self.anti_bot_config.insert(sale_id, &anti_bot_config);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_bonus_brackets(
+            &mut self,
+            sale_id: u32,
+            bonus_brackets: Vec<BonusBracket>,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            if bonus_brackets.len() > MAX_BONUS_BRACKETS {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Too many bonus brackets".to_string(),
+                ));
+            }
+            if bonus_brackets
+                .windows(2)
+                .any(|pair| pair[0].min_in_amount >= pair[1].min_in_amount)
+            {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus brackets must be in ascending order of min_in_amount".to_string(),
+                ));
+            }
+            if bonus_brackets
+                .iter()
+                .any(|bracket| bracket.bonus_percentage > 100)
+            {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus percentage must be less than or equal to 100".to_string(),
+                ));
+            }
+            // Pricing relies on out amount only growing with in amount
+            if bonus_brackets
+                .windows(2)
+                .any(|pair| pair[0].bonus_percentage > pair[1].bonus_percentage)
+            {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus percentages must not decrease".to_string(),
+                ));
+            }
+
+            self.bonus_brackets.insert(sale_id, &bonus_brackets);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_code(&mut self, code_hash: Hash) -> Result<()> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            self.env().set_code_hash(&code_hash)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_guardian(&mut self, guardian: Option<AccountId>) -> Result<()> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            self.guardian.set(&guardian);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_kyc_signer(&mut self, sale_id: u32, kyc_signer: Option<[u8; 20]>) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            match kyc_signer {
+                Some(kyc_signer) => {
+                    self.kyc_signer.insert(sale_id, &kyc_signer);
+                }
+                None => self.kyc_signer.remove(sale_id),
+            }
+
+            Ok(())
+        }
+
+        // The liquidity config can only be changed before anything is sold, as
+        // each purchase holds back AZERO according to it.
+        #[ink(message)]
+        pub fn set_liquidity_config(
+            &mut self,
+            sale_id: u32,
+            liquidity_config: LiquidityConfig,
+        ) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            if sale.sold > 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Liquidity config can only be set before the first purchase".to_string(),
+                ));
+            }
+            if liquidity_config.azero_percentage > 100 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "AZERO percentage must be less than or equal to 100".to_string(),
+                ));
+            }
+
+            self.liquidity_config.insert(sale_id, &liquidity_config);
+
+            Ok(())
+        }
+
+        // Metadata can only be changed before the sale starts, so that buyers
+        // never accept terms that change afterwards.
+        #[ink(message)]
+        pub fn set_metadata(&mut self, sale_id: u32, metadata: Metadata) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Pending {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            self.metadata.insert(sale_id, &metadata);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_mint_on_purchase(&mut self, sale_id: u32, mint_on_purchase: bool) -> Result<()> {
+            let mut sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            sale.mint_on_purchase = mint_on_purchase;
+            self.sales.insert(sale_id, &sale);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_price_oracle(
+            &mut self,
+            price_oracle: Option<AccountId>,
+            max_price_age: Timestamp,
+        ) -> Result<()> {
+            let mut config: Config = self.config();
+            Self::authorise(config.admin, Self::env().caller())?;
+            config.price_oracle = price_oracle;
+            config.max_price_age = max_price_age;
+            self.config.set(&config);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_screening_oracle(&mut self, screening_oracle: Option<AccountId>) -> Result<()> {
+            Self::authorise(self.config().admin, Self::env().caller())?;
+            self.screening_oracle.set(&screening_oracle);
+
+            Ok(())
+        }
+
+        // Tranches can only be changed before anything is sold, so that
+        // buyers know how the proceeds will be released.
+        #[ink(message)]
+        pub fn set_tranches(&mut self, sale_id: u32, tranches: Vec<Tranche>) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            if sale.sold > 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranches can only be set before the first purchase".to_string(),
+                ));
+            }
+            if tranches.len() > MAX_TRANCHES {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Too many tranches".to_string(),
+                ));
+            }
+            if tranches.iter().any(|tranche| tranche.percentage == 0) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche percentage must be positive".to_string(),
+                ));
+            }
+            if !tranches.is_empty()
+                && tranches
+                    .iter()
+                    .map(|tranche| u32::from(tranche.percentage))
+                    .sum::<u32>()
+                    != 100
+            {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche percentages must add up to 100".to_string(),
+                ));
+            }
+
+            if tranches.is_empty() {
+                self.tranches.remove(sale_id);
+            } else {
+                self.tranches.insert(sale_id, &tranches);
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_usd_price(&mut self, sale_id: u32, usd_price: Option<UsdPrice>) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.ensure_open(sale_id, &sale)?;
+            match usd_price {
+                Some(usd_price) => {
+                    if usd_price.usd_amount == 0 || usd_price.out_amount == 0 {
+                        return Err(AZTokenSaleError::UnprocessableEntity(
+                            "USD price amounts must be positive".to_string(),
+                        ));
+                    }
+                    if self.config().price_oracle.is_none() {
+                        return Err(AZTokenSaleError::UnprocessableEntity(
+                            "Price oracle is not set".to_string(),
+                        ));
+                    }
+                    self.usd_price.insert(sale_id, &usd_price);
+                }
+                None => self.usd_price.remove(sale_id),
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn unpause(&mut self, sale_id: u32) -> Result<()> {
+            let sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            self.transition(sale_id, &sale, SaleStatus::Active)
+        }
+
+        // Votes to unlock a tranche with the caller's out_token from the sale,
+        // counting no more than they received and still hold. Balances are read
+        // when voting, so tokens moved to another buyer after a vote can count
+        // again for them.
+        #[ink(message)]
+        pub fn vote_tranche(&mut self, sale_id: u32, index: u32) -> Result<Balance> {
+            let caller: AccountId = Self::env().caller();
+            let sale: Sale = self.sale(sale_id)?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Finalized {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            let tranche: Tranche = self
+                .tranches(sale_id)
+                .get(index as usize)
+                .cloned()
+                .ok_or(AZTokenSaleError::NotFound("Tranche".to_string()))?;
+            if tranche.unlock != TrancheUnlock::Vote {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche is not unlocked by vote".to_string(),
+                ));
+            }
+            if self.released_tranches.contains((sale_id, index)) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche already released".to_string(),
+                ));
+            }
+            if self.tranche_voters.contains((sale_id, index, caller)) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Already voted".to_string(),
+                ));
+            }
+            let contribution: Contribution = self.contribution(sale_id, caller);
+            if contribution.out_amount == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Only buyers can vote".to_string(),
+                ));
+            }
+
+            let weight: Balance = contribution
+                .out_amount
+                .min(PSP22Ref::balance_of(&sale.out_token, caller));
+            if weight == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "No out_token to vote with".to_string(),
+                ));
+            }
+            self.tranche_voters.insert((sale_id, index, caller), &());
+            self.tranche_votes.insert(
+                (sale_id, index),
+                &(self.tranche_votes(sale_id, index) + weight),
+            );
+
+            Ok(weight)
+        }
+
+        // Sends unsold and refunded out_token back to the admin once the sale is over.
+        #[ink(message)]
+        pub fn withdraw_inventory(&mut self, sale_id: u32) -> Result<Balance> {
+            let mut sale: Sale = self.sale(sale_id)?;
+            Self::authorise(sale.admin, Self::env().caller())?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if !matches!(status, SaleStatus::Finalized | SaleStatus::Cancelled) {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            if sale.inventory == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "No inventory to withdraw".to_string(),
+                ));
+            }
+
+            let inventory: Balance = sale.inventory;
+            sale.inventory = 0;
+            self.sales.insert(sale_id, &sale);
+            PSP22Ref::transfer_builder(&sale.out_token, sale.admin, inventory, vec![])
+                .call_flags(CallFlags::default())
+                .invoke()?;
+
+            Ok(inventory)
+        }
+
+        // === PRIVATE ===
+        // Works out a purchase without changing any state. buy and quote both
+        // go through here so that they can not diverge.
+        fn plan_buy(
+            &self,
+            sale_id: u32,
+            caller: AccountId,
+            in_amount: Balance,
+            terms_hash: Option<Hash>,
+        ) -> Result<BuyPlan> {
+            let sale: Sale = self.sale(sale_id)?;
+            let status: SaleStatus = self.sale_status(sale_id, &sale);
+            if status != SaleStatus::Active {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+            let bonus_brackets: Vec<BonusBracket> = self.bonus_brackets(sale_id);
+            let price: Price = self.price(sale_id, &sale, &bonus_brackets)?;
+            // validate in amount buys something
+            if in_amount == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount must be positive".to_string(),
+                ));
+            }
+            if in_amount < price.min_in_amount() {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount is too small to buy any out_token".to_string(),
+                ));
+            }
+            // validate caller is not blocked
+            if self.blocklisted(caller) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Account is blocked".to_string(),
+                ));
+            }
+            if let Some(screening_oracle) = self.screening_oracle() {
+                if ScreeningOracleRef::is_blocked_builder(&screening_oracle, caller)
+                    .call_flags(CallFlags::default())
+                    .invoke()
+                {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "Account is blocked".to_string(),
+                    ));
+                }
+            }
+            // validate caller agreed to the current terms
+            if let Some(current_terms_hash) = self
+                .metadata(sale_id)
+                .and_then(|metadata| metadata.terms_hash)
+            {
+                if terms_hash != Some(current_terms_hash) {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "Terms hash does not match".to_string(),
+                    ));
+                }
+            }
+            // validate allowlist, refunding whatever goes over the allocation
+            let mut allowed_in_amount: Balance = in_amount;
+            if self.allowlist_only(sale_id) {
+                let allocation: Allocation = self.allocation(sale_id, caller).ok_or(
+                    AZTokenSaleError::UnprocessableEntity("Not on the allowlist".to_string()),
+                )?;
+                let remaining_allocation: Balance =
+                    allocation.saturating_sub(self.contribution(sale_id, caller).in_amount);
+                if remaining_allocation < price.min_in_amount() {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "Allocation used up".to_string(),
+                    ));
+                }
+                allowed_in_amount = allowed_in_amount.min(remaining_allocation);
+            }
+            // validate anti-bot protections
+            let anti_bot_config: AntiBotConfig = self.anti_bot_config(sale_id);
+            let block_number: BlockNumber = Self::env().block_number();
+            if anti_bot_config.no_contract_callers && Self::env().is_contract(&caller) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Contract callers are not allowed".to_string(),
+                ));
+            }
+            if let Some(last_purchase_block) = self.last_purchase_blocks.get((sale_id, caller)) {
+                if block_number
+                    < last_purchase_block.saturating_add(anti_bot_config.cooldown_blocks)
+                {
+                    return Err(AZTokenSaleError::UnprocessableEntity(
+                        "Purchase cooldown has not elapsed".to_string(),
+                    ));
+                }
+            }
+            let block_out_amount: Balance = match self.block_out_amount.get(sale_id) {
+                Some((block, out_amount)) if block == block_number => out_amount,
+                _ => 0,
+            };
+            let block_remaining_out_amount: Option<Balance> = anti_bot_config
+                .max_out_amount_per_block
+                .map(|max_out_amount| max_out_amount.saturating_sub(block_out_amount));
+            if block_remaining_out_amount == Some(0) {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Block purchase limit reached".to_string(),
+                ));
+            }
+            // validate inventory is positive
+            let inventory: Balance = if sale.mint_on_purchase {
+                // out_token enforces its own cap when minting
+                Balance::MAX
+            } else {
+                sale.inventory
+            };
+            if inventory == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Sold out".to_string(),
+                ));
+            }
+
+            // Fill as much as is available and refund the rest, including dust
+            let available_out_amount: Balance = match block_remaining_out_amount {
+                Some(remaining) => remaining.min(inventory),
+                None => inventory,
+            };
+            let mut fill: Fill = price.fill(allowed_in_amount, available_out_amount);
+            fill.refund += in_amount - allowed_in_amount;
+            if fill.out_amount == 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Sold out".to_string(),
+                ));
+            }
+            let inventory_after: Balance = if sale.mint_on_purchase {
+                inventory
+            } else {
+                inventory - fill.out_amount
+            };
+            let price_after: Option<Balance> = Some(price.min_in_amount())
+                .filter(|min_in_amount| price.fill(*min_in_amount, inventory_after).out_amount > 0);
+
+            Ok(BuyPlan {
+                sale,
+                anti_bot_config,
+                block_number,
+                block_out_amount,
+                fill,
+                price_after,
+            })
+        }
+
+        fn process_buy(
+            &mut self,
+            sale_id: u32,
+            caller: AccountId,
+            in_amount: Balance,
+            terms_hash: Option<Hash>,
+        ) -> Result<(Balance, Balance)> {
+            let BuyPlan {
+                mut sale,
+                anti_bot_config,
+                block_number,
+                mut block_out_amount,
+                fill,
+                ..
+            } = self.plan_buy(sale_id, caller, in_amount, terms_hash)?;
+            let mut liquidity: Liquidity = self.liquidity(sale_id);
+            if fill.refund > 0 {
+                self.transfer_azero(caller, fill.refund)?;
+            }
+            let in_amount: Balance = fill.accepted_in;
+            let out_amount: Balance = fill.out_amount;
+
+            // Trasfer out token to user, measuring what was actually sent and
+            // received in case out_token takes a fee on transfer
+            let caller_balance_before: Balance = PSP22Ref::balance_of(&sale.out_token, caller);
+            let sent_amount: Balance = if sale.mint_on_purchase {
+                PSP22MintableRef::mint_builder(&sale.out_token, caller, out_amount)
+                    .call_flags(CallFlags::default())
+                    .invoke()?;
+                out_amount
+            } else {
+                let contract_address: AccountId = Self::env().account_id();
+                let contract_balance_before: Balance =
+                    PSP22Ref::balance_of(&sale.out_token, contract_address);
+                PSP22Ref::transfer_builder(&sale.out_token, caller, out_amount, vec![])
+                    .call_flags(CallFlags::default())
+                    .invoke()?;
+                let sent_amount: Balance = contract_balance_before
+                    - PSP22Ref::balance_of(&sale.out_token, contract_address);
+                sale.inventory = sale.inventory.saturating_sub(sent_amount);
+                sent_amount
+            };
+            let received_amount: Balance =
+                PSP22Ref::balance_of(&sale.out_token, caller) - caller_balance_before;
+
+            // Hold back AZERO for liquidity and escrow the rest
+            let mut liquidity_azero_amount: Balance = 0;
+            if let Some(liquidity_config) = self.liquidity_config(sale_id) {
+                liquidity_azero_amount = (U256::from(in_amount)
+                    * U256::from(liquidity_config.azero_percentage)
+                    / U256::from(100))
+                .as_u128();
+                liquidity.azero_amount += liquidity_azero_amount;
+                self.liquidity.insert(sale_id, &liquidity);
+            }
+            sale.proceeds += in_amount - liquidity_azero_amount;
+            sale.sold += received_amount;
+            self.sales.insert(sale_id, &sale);
+
+            // Record contribution for refunds
+            let mut contribution: Contribution = self.contribution(sale_id, caller);
+            contribution.in_amount += in_amount;
+            contribution.out_amount += received_amount;
+            self.contributions.insert((sale_id, caller), &contribution);
+
+            // Record purchase for anti-bot protections
+            if anti_bot_config.cooldown_blocks > 0 {
+                self.last_purchase_blocks
+                    .insert((sale_id, caller), &block_number);
+            }
+            if anti_bot_config.max_out_amount_per_block.is_some() {
+                block_out_amount += sent_amount;
+                self.block_out_amount
+                    .insert(sale_id, &(block_number, block_out_amount));
+            }
+
+            Ok((in_amount, received_amount))
+        }
+
+        fn sale_status(&self, sale_id: u32, sale: &Sale) -> SaleStatus {
+            if let Some(status) = self.status.get(sale_id) {
+                return status;
+            }
+
+            let block_timestamp: Timestamp = Self::env().block_timestamp();
+            if block_timestamp < sale.start {
+                SaleStatus::Pending
+            } else if matches!(sale.end, Some(end) if block_timestamp >= end) {
+                SaleStatus::Ended
+            } else {
+                SaleStatus::Active
+            }
+        }
+
+        // Sales priced in USD are converted to AZERO at the oracle's price.
+        fn price<'a>(
+            &self,
+            sale_id: u32,
+            sale: &Sale,
+            bonus_brackets: &'a [BonusBracket],
+        ) -> Result<Price<'a>> {
+            let usd_price: UsdPrice = match self.usd_price(sale_id) {
+                Some(usd_price) => usd_price,
+                None => return Ok(Price::new(sale, bonus_brackets)),
+            };
+            let config: Config = self.config();
+            let price_oracle: AccountId =
+                config
+                    .price_oracle
+                    .ok_or(AZTokenSaleError::UnprocessableEntity(
+                        "Price oracle is not set".to_string(),
+                    ))?;
+            let price_data: PriceData = PriceOracleRef::azero_usd_price_builder(&price_oracle)
+                .call_flags(CallFlags::default())
+                .invoke();
+            if Self::env()
+                .block_timestamp()
+                .saturating_sub(price_data.timestamp)
+                > config.max_price_age
+            {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Oracle price is stale".to_string(),
+                ));
+            }
+
+            Price::from_usd(
+                usd_price.usd_amount,
+                usd_price.out_amount,
+                price_data.price,
+                bonus_brackets,
+            )
+            .ok_or(AZTokenSaleError::UnprocessableEntity(
+                "Oracle price can not be used".to_string(),
+            ))
+        }
+
+        fn sale_tranches(&self, sale_id: u32) -> Vec<Tranche> {
+            let tranches: Vec<Tranche> = self.tranches(sale_id);
+            if tranches.is_empty() {
+                vec![Tranche {
+                    percentage: 100,
+                    unlock: TrancheUnlock::Timestamp(0),
+                }]
+            } else {
+                tranches
+            }
+        }
+
+        // Finalized and cancelled sales can no longer be changed.
+        fn ensure_open(&self, sale_id: u32, sale: &Sale) -> Result<()> {
+            let status: SaleStatus = self.sale_status(sale_id, sale);
+            if matches!(status, SaleStatus::Finalized | SaleStatus::Cancelled) {
+                return Err(AZTokenSaleError::InvalidSaleStatus(status));
+            }
+
+            Ok(())
+        }
+
+        // Moving to Active means unpausing, after which the window decides
+        // the status again.
+        fn transition(&mut self, sale_id: u32, sale: &Sale, to: SaleStatus) -> Result<()> {
+            let from: SaleStatus = self.sale_status(sale_id, sale);
+            let allowed: bool = match to {
+                SaleStatus::Active => from == SaleStatus::Paused,
+                SaleStatus::Paused => matches!(from, SaleStatus::Pending | SaleStatus::Active),
+                SaleStatus::Finalized => matches!(
+                    from,
+                    SaleStatus::Active | SaleStatus::Paused | SaleStatus::Ended
+                ),
+                SaleStatus::Cancelled => {
+                    !matches!(from, SaleStatus::Finalized | SaleStatus::Cancelled)
+                }
+                SaleStatus::Pending | SaleStatus::Ended => false,
+            };
+            if !allowed {
+                return Err(AZTokenSaleError::InvalidTransition(from, to));
+            }
+
+            if to == SaleStatus::Active {
+                self.status.remove(sale_id);
+            } else {
+                self.status.insert(sale_id, &to);
+            }
+
+            Ok(())
+        }
+
+        // v1 held a single sale, which becomes sale 0 with the same admin as
+        // the contract. Cooldowns and used voucher nonces start afresh, as
+        // vouchers are now signed over the sale id as well.
+        fn migrate_from_v1(&mut self) -> Result<()> {
+            let config_v1: ConfigV1 =
+                ink::env::get_contract_storage::<u32, ConfigV1>(&CONFIG_STORAGE_KEY)?
+                    .ok_or(AZTokenSaleError::NotFound("Config".to_string()))?;
+            let mint_on_purchase: bool =
+                Self::take_v1_value(V1_MINT_ON_PURCHASE_KEY)?.unwrap_or_default();
+            // v1 sold whatever out_token the contract held
+            let inventory: Balance = if mint_on_purchase {
+                0
+            } else {
+                PSP22Ref::balance_of(&config_v1.out_token, Self::env().account_id())
+            };
+
+            self.config.set(&Config {
+                version: STORAGE_VERSION,
+                admin: config_v1.admin,
+                price_oracle: None,
+                max_price_age: 0,
+            });
+            self.sales.insert(
+                0,
+                &Sale {
+                    admin: config_v1.admin,
+                    out_token: config_v1.out_token,
+                    in_unit: config_v1.in_unit,
+                    out_unit: config_v1.out_unit,
+                    inventory,
+                    proceeds: 0,
+                    released: 0,
+                    sold: 0,
+                    start: 0,
+                    end: None,
+                    mint_on_purchase,
+                },
+            );
+            self.sales_count.set(&1);
+            if let Some(Some(kyc_signer)) =
+                Self::take_v1_value::<Option<[u8; 20]>>(V1_KYC_SIGNER_KEY)?
+            {
+                self.kyc_signer.insert(0, &kyc_signer);
+            }
+            if let Some(anti_bot_config) =
+                Self::take_v1_value::<AntiBotConfig>(V1_ANTI_BOT_CONFIG_KEY)?
+            {
+                self.anti_bot_config.insert(0, &anti_bot_config);
+            }
+            if let Some(liquidity_v1) = Self::take_v1_value::<LiquidityV1>(V1_LIQUIDITY_KEY)? {
+                self.liquidity.insert(
+                    0,
+                    &Liquidity {
+                        azero_amount: liquidity_v1.azero_amount,
+                        lp_amount: liquidity_v1.lp_amount,
+                    },
+                );
+                if liquidity_v1.finalized {
+                    self.status.insert(0, &SaleStatus::Finalized);
+                }
+            }
+            if let Some(Some(liquidity_config)) =
+                Self::take_v1_value::<Option<LiquidityConfig>>(V1_LIQUIDITY_CONFIG_KEY)?
+            {
+                self.liquidity_config.insert(0, &liquidity_config);
+            }
+            if let Some(bonus_brackets) =
+                Self::take_v1_value::<Vec<BonusBracket>>(V1_BONUS_BRACKETS_KEY)?
+            {
+                self.bonus_brackets.insert(0, &bonus_brackets);
+            }
+            Self::take_v1_value::<(BlockNumber, Balance)>(V1_BLOCK_OUT_AMOUNT_KEY)?;
+
+            Ok(())
+        }
+
+        // Reads and clears a value stored by v1.
+        fn take_v1_value<T: Storable>(key: u32) -> Result<Option<T>> {
+            let value: Option<T> = ink::env::get_contract_storage::<u32, T>(&key)?;
+            ink::env::clear_contract_storage::<u32>(&key);
+
+            Ok(value)
+        }
+
+        fn authorise(allowed: AccountId, received: AccountId) -> Result<()> {
+            if allowed != received {
+                return Err(AZTokenSaleError::Unauthorised);
+            }
+
+            Ok(())
+        }
+
+        // Returns the amount received, which is less than amount for
+        // fee-on-transfer tokens.
+        fn add_inventory(
+            &mut self,
+            sale_id: u32,
+            mut sale: Sale,
+            from: AccountId,
+            amount: Balance,
+        ) -> Result<Balance> {
+            self.ensure_open(sale_id, &sale)?;
+            // validate in amount is in units of in_unit
+            if amount == 0 || amount % sale.out_unit > 0 {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Amount must be in multiples of out_unit".to_string(),
+                ));
+            }
+
+            let received_amount: Balance = self.acquire_psp22(sale.out_token, from, amount)?;
+            sale.inventory += received_amount;
+            self.sales.insert(sale_id, &sale);
+
+            Ok(received_amount)
+        }
+
+        // Returns the amount actually received.
+        fn acquire_psp22(
+            &self,
+            token: AccountId,
+            from: AccountId,
+            amount: Balance,
+        ) -> Result<Balance> {
+            let contract_address: AccountId = self.env().account_id();
+            let balance_before: Balance = PSP22Ref::balance_of(&token, contract_address);
+            PSP22Ref::transfer_from_builder(&token, from, contract_address, amount, vec![])
+                .call_flags(CallFlags::default())
+                .invoke()?;
+
+            Ok(PSP22Ref::balance_of(&token, contract_address) - balance_before)
+        }
+
+        fn transfer_azero(&self, address: AccountId, amount: Balance) -> Result<()> {
+            if self.env().transfer(address, amount).is_err() {
+                return Err(AZTokenSaleError::UnprocessableEntity(
+                    "Insufficient AZERO balance".to_string(),
+                ));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::{
+            hash::{CryptoHash, HashOutput, Keccak256},
+            test::{callee, default_accounts, set_caller, DefaultAccounts},
+            DefaultEnvironment,
+        };
+        use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+        // === CONSTANTS ===
+        const MOCK_IN_UNIT: Balance = 250;
+        const MOCK_OUT_UNIT: Balance = 1;
+        const MOCK_SALE_ID: u32 = 0;
+        const MOCK_START: Timestamp = 10;
+        const MOCK_END: Timestamp = 20;
+
+        // === HELPERS ===
+        fn init() -> (DefaultAccounts<DefaultEnvironment>, AZTokenSale) {
+            let accounts = default_accounts();
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut token_sale = AZTokenSale::new();
+            token_sale
+                .create_sale(
+                    accounts.alice,
+                    accounts.eve,
+                    MOCK_IN_UNIT,
+                    MOCK_OUT_UNIT,
+                    0,
+                    None,
+                )
+                .unwrap();
+            (accounts, token_sale)
+        }
+
+        fn create_windowed_sale(
+            accounts: &DefaultAccounts<DefaultEnvironment>,
+            az_token_sale: &mut AZTokenSale,
+        ) -> u32 {
+            az_token_sale
+                .create_sale(
+                    accounts.alice,
+                    accounts.eve,
+                    MOCK_IN_UNIT,
+                    MOCK_OUT_UNIT,
+                    MOCK_START,
+                    Some(MOCK_END),
+                )
+                .unwrap()
+        }
+
+        // Returns the Ethereum address of the key and its signature over the voucher.
+        fn sign_voucher(
+            secret_key: [u8; 32],
+            sale_id: u32,
+            voucher: &Voucher,
+        ) -> ([u8; 20], [u8; 65]) {
+            let secp = Secp256k1::new();
+            let secret_key = SecretKey::from_slice(&secret_key).unwrap();
+            let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+            let mut public_key_hash = <Keccak256 as HashOutput>::Type::default();
+            <Keccak256 as CryptoHash>::hash(
+                &public_key.serialize_uncompressed()[1..],
+                &mut public_key_hash,
+            );
+            let mut address = [0; 20];
+            address.copy_from_slice(&public_key_hash[12..]);
+            let mut message_hash = <Blake2x256 as HashOutput>::Type::default();
+            <Blake2x256 as CryptoHash>::hash(
+                &scale::Encode::encode(&(callee::<DefaultEnvironment>(), sale_id, voucher)),
+                &mut message_hash,
+            );
+            let (recovery_id, compact) = secp
+                .sign_ecdsa_recoverable(&Message::from_slice(&message_hash).unwrap(), &secret_key)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            (address, signature)
+        }
+
+        fn mock_metadata() -> Metadata {
+            Metadata {
+                name: "Button".to_string(),
+                description_uri: "ipfs://description".to_string(),
+                website: "https://btn.group".to_string(),
+                logo_hash: Some(Hash::from([1; 32])),
+                terms_hash: Some(Hash::from([2; 32])),
+            }
+        }
+
+        // Puts a sale from create_windowed_sale into the given status.
+        fn set_status(az_token_sale: &mut AZTokenSale, sale_id: u32, status: SaleStatus) {
+            let block_timestamp: Timestamp = match status {
+                SaleStatus::Pending => MOCK_START - 1,
+                SaleStatus::Ended => MOCK_END,
+                _ => MOCK_START,
+            };
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(block_timestamp);
+            match status {
+                SaleStatus::Pending | SaleStatus::Active | SaleStatus::Ended => {
+                    az_token_sale.status.remove(sale_id)
+                }
+                _ => {
+                    az_token_sale.status.insert(sale_id, &status);
+                }
+            }
+        }
+
+        fn mock_bonus_brackets() -> Vec<BonusBracket> {
+            vec![
+                BonusBracket {
+                    min_in_amount: MOCK_IN_UNIT * 100,
+                    bonus_percentage: 5,
+                },
+                BonusBracket {
+                    min_in_amount: MOCK_IN_UNIT * 1_000,
+                    bonus_percentage: 10,
+                },
+            ]
+        }
+
+        fn mock_tranches() -> Vec<Tranche> {
+            vec![
+                Tranche {
+                    percentage: 40,
+                    unlock: TrancheUnlock::Timestamp(MOCK_END + 10),
+                },
+                Tranche {
+                    percentage: 60,
+                    unlock: TrancheUnlock::Vote,
+                },
+            ]
+        }
+
+        fn mock_liquidity_config(
+            accounts: &DefaultAccounts<DefaultEnvironment>,
+        ) -> LiquidityConfig {
+            LiquidityConfig {
+                router: accounts.frank,
+                lp_token: accounts.django,
+                azero_percentage: 50,
+                lp_unlock_timestamp: 1_000,
+            }
+        }
+
+        // === TESTS ===
+        // === TEST QUERIES ===
+        #[ink::test]
+        fn test_allowlist_only() {
+            let (_accounts, mut az_token_sale) = init();
+            // when no allowlist phase has been set
+            // * it is false
+            assert!(!az_token_sale.allowlist_only(MOCK_SALE_ID));
+            // when the allowlist phase has no public_at
+            // * it is true
+            az_token_sale
+                .allowlist_phase
+                .insert(MOCK_SALE_ID, &None::<Timestamp>);
+            assert!(az_token_sale.allowlist_only(MOCK_SALE_ID));
+            // when the allowlist phase has a public_at
+            az_token_sale
+                .allowlist_phase
+                .insert(MOCK_SALE_ID, &Some(MOCK_START));
+            // = * it is true before public_at
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_START - 1);
+            assert!(az_token_sale.allowlist_only(MOCK_SALE_ID));
+            // = * it is false from public_at
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_START);
+            assert!(!az_token_sale.allowlist_only(MOCK_SALE_ID));
+        }
+
+        #[ink::test]
+        fn test_config() {
+            let (accounts, token_sale) = init();
+            let config = token_sale.config();
+            // * it returns the config
+            assert_eq!(config.version, STORAGE_VERSION);
+            assert_eq!(config.admin, accounts.alice);
+            assert_eq!(config.price_oracle, None);
+            assert_eq!(config.max_price_age, 0);
+        }
+
+        #[ink::test]
+        fn test_locked() {
+            let (_accounts, mut az_token_sale) = init();
+            // when sale does not exist
+            // * it raises an error
+            assert_eq!(
+                az_token_sale.locked(MOCK_SALE_ID + 1),
+                Err(AZTokenSaleError::NotFound("Sale".to_string()))
+            );
+            // when sale exists
+            // * it returns the proceeds held in escrow
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.proceeds = MOCK_IN_UNIT;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            assert_eq!(az_token_sale.locked(MOCK_SALE_ID), Ok(MOCK_IN_UNIT));
+        }
+
+        #[ink::test]
+        fn test_metadata() {
+            let (_accounts, mut az_token_sale) = init();
+            // when metadata has not been set
+            // * it returns None
+            assert_eq!(az_token_sale.metadata(MOCK_SALE_ID), None);
+            // when metadata has been set
+            // * it returns the metadata
+            az_token_sale
+                .metadata
+                .insert(MOCK_SALE_ID, &mock_metadata());
+            assert_eq!(az_token_sale.metadata(MOCK_SALE_ID), Some(mock_metadata()));
+        }
+
+        #[ink::test]
+        fn test_quote() {
+            let (accounts, mut az_token_sale) = init();
+            // when buy would fail
+            // * it raises the same error
+            let windowed_sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            set_status(&mut az_token_sale, windowed_sale_id, SaleStatus::Paused);
+            for (sale_id, in_amount) in [
+                (MOCK_SALE_ID + 2, MOCK_IN_UNIT),
+                (windowed_sale_id, MOCK_IN_UNIT),
+                (MOCK_SALE_ID, 0),
+                (MOCK_SALE_ID, MOCK_IN_UNIT - 1),
+                (MOCK_SALE_ID, MOCK_IN_UNIT),
+            ] {
+                ink::env::test::set_value_transferred::<DefaultEnvironment>(in_amount);
+                let result = az_token_sale.quote(sale_id, in_amount, None);
+                assert!(result.is_err());
+                assert_eq!(result.err(), az_token_sale.buy(sale_id, None).err());
+            }
+            // when buy would succeed
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.inventory = MOCK_OUT_UNIT * 3;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            // = when stock remains afterwards
+            // = * it quotes the fill, refunding dust, and the price of the next purchase
+            assert_eq!(
+                az_token_sale.quote(MOCK_SALE_ID, MOCK_IN_UNIT * 2 + 1, None),
+                Ok(Quote {
+                    accepted_in: MOCK_IN_UNIT * 2,
+                    out_amount: MOCK_OUT_UNIT * 2,
+                    refund: 1,
+                    price_after: Some(MOCK_IN_UNIT)
+                })
+            );
+            // = when it sells out
+            // = * it quotes the partial fill without a price afterwards
+            assert_eq!(
+                az_token_sale.quote(MOCK_SALE_ID, MOCK_IN_UNIT * 5, None),
+                Ok(Quote {
+                    accepted_in: MOCK_IN_UNIT * 3,
+                    out_amount: MOCK_OUT_UNIT * 3,
+                    refund: MOCK_IN_UNIT * 2,
+                    price_after: None
+                })
+            );
+            // = when sale is allowlist-only
+            // = * it refunds whatever goes over the caller's allocation
+            az_token_sale
+                .set_allowlist_phase(MOCK_SALE_ID, None)
+                .unwrap();
+            az_token_sale
+                .add_to_allowlist(MOCK_SALE_ID, vec![(accounts.alice, MOCK_IN_UNIT + 1)])
+                .unwrap();
+            assert_eq!(
+                az_token_sale.quote(MOCK_SALE_ID, MOCK_IN_UNIT * 2, None),
+                Ok(Quote {
+                    accepted_in: MOCK_IN_UNIT,
+                    out_amount: MOCK_OUT_UNIT,
+                    refund: MOCK_IN_UNIT,
+                    price_after: Some(MOCK_IN_UNIT)
+                })
+            );
+            az_token_sale.end_allowlist_phase(MOCK_SALE_ID).unwrap();
+            // = when sale has terms
+            // = * it quotes for callers passing in their hash
+            az_token_sale
+                .metadata
+                .insert(MOCK_SALE_ID, &mock_metadata());
+            assert_eq!(
+                az_token_sale.quote(MOCK_SALE_ID, MOCK_IN_UNIT, mock_metadata().terms_hash),
+                Ok(Quote {
+                    accepted_in: MOCK_IN_UNIT,
+                    out_amount: MOCK_OUT_UNIT,
+                    refund: 0,
+                    price_after: Some(MOCK_IN_UNIT)
+                })
+            );
+            az_token_sale.metadata.remove(MOCK_SALE_ID);
+            // = when minting on purchase
+            // = * it never sells out
+            az_token_sale
+                .set_mint_on_purchase(MOCK_SALE_ID, true)
+                .unwrap();
+            assert_eq!(
+                az_token_sale.quote(MOCK_SALE_ID, MOCK_IN_UNIT * 5, None),
+                Ok(Quote {
+                    accepted_in: MOCK_IN_UNIT * 5,
+                    out_amount: MOCK_OUT_UNIT * 5,
+                    refund: 0,
+                    price_after: Some(MOCK_IN_UNIT)
+                })
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS BUY CALLS THE OUT_TOKEN SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_released() {
+            let (_accounts, mut az_token_sale) = init();
+            // when sale does not exist
+            // * it raises an error
+            assert_eq!(
+                az_token_sale.released(MOCK_SALE_ID + 1),
+                Err(AZTokenSaleError::NotFound("Sale".to_string()))
+            );
+            // when sale exists
+            // * it returns the proceeds released to the admin
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.released = MOCK_IN_UNIT;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            assert_eq!(az_token_sale.released(MOCK_SALE_ID), Ok(MOCK_IN_UNIT));
+        }
+
+        #[ink::test]
+        fn test_sale() {
+            let (accounts, token_sale) = init();
+            // when sale exists
+            // * it returns the sale
+            assert_eq!(
+                token_sale.sale(MOCK_SALE_ID),
+                Ok(Sale {
+                    admin: accounts.alice,
+                    out_token: accounts.eve,
+                    in_unit: MOCK_IN_UNIT,
+                    out_unit: MOCK_OUT_UNIT,
+                    inventory: 0,
+                    proceeds: 0,
+                    released: 0,
+                    sold: 0,
+                    start: 0,
+                    end: None,
+                    mint_on_purchase: false
+                })
+            );
+            // when sale does not exist
+            // * it raises an error
+            assert_eq!(
+                token_sale.sale(MOCK_SALE_ID + 1),
+                Err(AZTokenSaleError::NotFound("Sale".to_string()))
+            );
+        }
+
+        #[ink::test]
+        fn test_status() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when sale does not exist
+            // * it raises an error
+            assert_eq!(
+                az_token_sale.status(sale_id + 1),
+                Err(AZTokenSaleError::NotFound("Sale".to_string()))
+            );
+            // when no status has been set
+            // = * it is Pending before the window
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_START - 1);
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Pending));
+            // = * it is Active during the window
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_START);
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Active));
+            // = * it is Ended after the window
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_END);
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Ended));
+            // = * it stays Active without an end
+            assert_eq!(az_token_sale.status(MOCK_SALE_ID), Ok(SaleStatus::Active));
+            // when a status has been set
+            // * it returns that status whatever the window
+            az_token_sale.status.insert(sale_id, &SaleStatus::Paused);
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Paused));
+        }
+
+        // === TEST HANDLES ===
+        #[ink::test]
+        fn test_add_amount_for_sale() {
+            let (accounts, mut az_token_sale) = init();
+            // when sale does not exist
+            // * it raises an error
+            let mut result = az_token_sale.add_amount_for_sale(MOCK_SALE_ID + 1, MOCK_OUT_UNIT);
+            assert_eq!(result, Err(AZTokenSaleError::NotFound("Sale".to_string())));
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            result = az_token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // = when amount is zero
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            result = az_token_sale.add_amount_for_sale(MOCK_SALE_ID, 0);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Amount must be in multiples of out_unit".to_string()
+                ))
+            );
+            // = when sale has been finalized or cancelled
+            // = * it raises an error
+            for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+                az_token_sale.status.insert(MOCK_SALE_ID, &status);
+                result = az_token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT);
+                assert_eq!(result, Err(AZTokenSaleError::InvalidSaleStatus(status)));
+            }
+            az_token_sale.status.remove(MOCK_SALE_ID);
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS BUTTON SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_add_amount_for_sale_with_permit() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.add_amount_for_sale_with_permit(
+                MOCK_SALE_ID,
+                accounts.bob,
+                MOCK_OUT_UNIT,
+                10,
+                [0; 65],
+            );
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS BUTTON SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_add_to_allowlist() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result =
+                az_token_sale.add_to_allowlist(MOCK_SALE_ID, vec![(accounts.bob, MOCK_IN_UNIT)]);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has been cancelled
+            // = * it raises an error
+            let windowed_sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            set_status(&mut az_token_sale, windowed_sale_id, SaleStatus::Cancelled);
+            result = az_token_sale
+                .add_to_allowlist(windowed_sale_id, vec![(accounts.bob, MOCK_IN_UNIT)]);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Cancelled))
+            );
+            // = when sale is open
+            // = * it adds and updates allocations
+            az_token_sale
+                .add_to_allowlist(
+                    MOCK_SALE_ID,
+                    vec![
+                        (accounts.bob, MOCK_IN_UNIT),
+                        (accounts.charlie, MOCK_IN_UNIT * 2),
+                    ],
+                )
+                .unwrap();
+            az_token_sale
+                .add_to_allowlist(MOCK_SALE_ID, vec![(accounts.bob, MOCK_IN_UNIT * 3)])
+                .unwrap();
+            assert_eq!(
+                az_token_sale.allocation(MOCK_SALE_ID, accounts.bob),
+                Some(MOCK_IN_UNIT * 3)
+            );
+            assert_eq!(
+                az_token_sale.allocation(MOCK_SALE_ID, accounts.charlie),
+                Some(MOCK_IN_UNIT * 2)
+            );
+            assert_eq!(
+                az_token_sale.allocation(windowed_sale_id, accounts.bob),
+                None
+            );
+        }
+
+        #[ink::test]
+        fn test_add_to_blocklist() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.add_to_blocklist(vec![accounts.bob]);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it blocks the accounts
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .add_to_blocklist(vec![accounts.bob, accounts.charlie])
+                .unwrap();
+            assert!(az_token_sale.blocklisted(accounts.bob));
+            assert!(az_token_sale.blocklisted(accounts.charlie));
+            assert!(!az_token_sale.blocklisted(accounts.django));
+        }
+
+        #[ink::test]
+        fn test_buy() {
+            let (accounts, mut az_token_sale) = init();
+
+            // when sale does not exist
+            // * it raises an error
+            let mut result = az_token_sale.buy(MOCK_SALE_ID + 1, None);
+            assert_eq!(result, Err(AZTokenSaleError::NotFound("Sale".to_string())));
+            // when sale is not active
+            // * it raises an error
+            let windowed_sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            for status in [
+                SaleStatus::Pending,
+                SaleStatus::Paused,
+                SaleStatus::Ended,
+                SaleStatus::Finalized,
+                SaleStatus::Cancelled,
+            ] {
+                set_status(&mut az_token_sale, windowed_sale_id, status);
+                result = az_token_sale.buy(windowed_sale_id, None);
+                assert_eq!(result, Err(AZTokenSaleError::InvalidSaleStatus(status)));
+            }
+            // when a KYC signer is set
+            // * it raises an error
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some([2; 20]))
+                .unwrap();
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Voucher required".to_string()
+                ))
+            );
+            az_token_sale.set_kyc_signer(MOCK_SALE_ID, None).unwrap();
+            // when in amount is zero
+            // * it raises an error
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount must be positive".to_string()
+                ))
+            );
+            // when in amount is positive
+            // = when in amount is too small to buy any out_token
+            // = * it raises an error
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(MOCK_IN_UNIT - 1);
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount is too small to buy any out_token".to_string()
+                ))
+            );
+            // = when in amount buys out_token
+            ink::env::test::set_value_transferred::<DefaultEnvironment>(MOCK_IN_UNIT);
+            // == when caller is on the blocklist
+            // == * it raises an error
+            az_token_sale
+                .add_to_blocklist(vec![accounts.alice])
+                .unwrap();
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Account is blocked".to_string()
+                ))
+            );
+            az_token_sale
+                .remove_from_blocklist(vec![accounts.alice])
+                .unwrap();
+            // == when sale has terms
+            // == * it raises an error unless their hash is passed in
+            az_token_sale
+                .metadata
+                .insert(MOCK_SALE_ID, &mock_metadata());
+            for terms_hash in [None, Some(Hash::from([3; 32]))] {
+                result = az_token_sale.buy(MOCK_SALE_ID, terms_hash);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::UnprocessableEntity(
+                        "Terms hash does not match".to_string()
+                    ))
+                );
+            }
+            az_token_sale.metadata.remove(MOCK_SALE_ID);
+            // == when sale is allowlist-only
+            az_token_sale
+                .set_allowlist_phase(MOCK_SALE_ID, None)
+                .unwrap();
+            // === when caller is not on the allowlist
+            // === * it raises an error
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Not on the allowlist".to_string()
+                ))
+            );
+            // === when caller has used up their allocation
+            // === * it raises an error
+            az_token_sale
+                .add_to_allowlist(MOCK_SALE_ID, vec![(accounts.alice, MOCK_IN_UNIT * 2)])
+                .unwrap();
+            az_token_sale.contributions.insert(
+                (MOCK_SALE_ID, accounts.alice),
+                &Contribution {
+                    in_amount: MOCK_IN_UNIT * 2 - 1,
+                    out_amount: MOCK_OUT_UNIT,
+                },
+            );
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Allocation used up".to_string()
+                ))
+            );
+            az_token_sale
+                .contributions
+                .remove((MOCK_SALE_ID, accounts.alice));
+            az_token_sale.end_allowlist_phase(MOCK_SALE_ID).unwrap();
+            // == when contract callers are not allowed
+            az_token_sale
+                .set_anti_bot_config(
+                    MOCK_SALE_ID,
+                    AntiBotConfig {
+                        no_contract_callers: true,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            // === when caller is a contract
+            // === * it raises an error
+            ink::env::test::set_contract::<DefaultEnvironment>(accounts.django);
+            set_caller::<DefaultEnvironment>(accounts.django);
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Contract callers are not allowed".to_string()
+                ))
+            );
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // == when a cooldown is set
+            az_token_sale
+                .set_anti_bot_config(
+                    MOCK_SALE_ID,
+                    AntiBotConfig {
+                        cooldown_blocks: 2,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            // === when caller purchased within the cooldown
+            // === * it raises an error
+            az_token_sale
+                .last_purchase_blocks
+                .insert((MOCK_SALE_ID, accounts.alice), &0);
+            ink::env::test::advance_block::<DefaultEnvironment>();
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Purchase cooldown has not elapsed".to_string()
+                ))
+            );
+            // == when a max out amount per block is set
+            az_token_sale
+                .set_anti_bot_config(
+                    MOCK_SALE_ID,
+                    AntiBotConfig {
+                        max_out_amount_per_block: Some(MOCK_OUT_UNIT * 10),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            // === when the limit has been reached this block
+            // === * it raises an error
+            az_token_sale.block_out_amount.insert(
+                MOCK_SALE_ID,
+                &(
+                    ink::env::block_number::<DefaultEnvironment>(),
+                    MOCK_OUT_UNIT * 10,
+                ),
+            );
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Block purchase limit reached".to_string()
+                ))
+            );
+            az_token_sale
+                .set_anti_bot_config(MOCK_SALE_ID, AntiBotConfig::default())
+                .unwrap();
+            // == when there is no inventory
+            // == * it raises an error
+            result = az_token_sale.buy(MOCK_SALE_ID, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Sold out".to_string()
+                ))
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS AIRDROP SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_buy_with_voucher() {
+            let (accounts, mut az_token_sale) = init();
+            let mut voucher = Voucher {
+                buyer: accounts.alice,
+                max_allocation: MOCK_IN_UNIT,
+                expiry: 10,
+                nonce: 0,
+            };
+
+            // when vouchers are not enabled
+            // * it raises an error
+            let mut result =
+                az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Vouchers are not enabled".to_string()
+                ))
+            );
+            // when vouchers are enabled
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some([2; 20]))
+                .unwrap();
+            // = when voucher is for someone else
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when voucher has expired
+            // = * it raises an error
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(voucher.expiry + 1);
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Voucher has expired".to_string()
+                ))
+            );
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(voucher.expiry);
+            // = when voucher nonce has been used
+            // = * it raises an error
+            az_token_sale
+                .invalidate_voucher_nonce(MOCK_SALE_ID, voucher.buyer, voucher.nonce)
+                .unwrap();
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Voucher has already been used".to_string()
+                ))
+            );
+            voucher.nonce += 1;
+            // = when in amount exceeds the voucher allocation
+            // = * it raises an error
+            ink::env::test::set_value_transferred::<DefaultEnvironment>(MOCK_IN_UNIT * 2);
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount exceeds voucher allocation".to_string()
+                ))
+            );
+            ink::env::test::set_value_transferred::<DefaultEnvironment>(MOCK_IN_UNIT);
+            // = when signature is not from the KYC signer
+            // = * it raises an error
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), [0; 65], None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Invalid signature".to_string()
+                ))
+            );
+            // = * it does not use up the nonce
+            assert!(!az_token_sale.voucher_nonce_used(MOCK_SALE_ID, voucher.buyer, voucher.nonce));
+            // = when signature is from the KYC signer
+            let (kyc_signer, signature) = sign_voucher([7; 32], MOCK_SALE_ID, &voucher);
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some(kyc_signer))
+                .unwrap();
+            // == when voucher was signed for another sale
+            // == * it raises an error
+            let (_, other_sale_signature) = sign_voucher([7; 32], MOCK_SALE_ID + 1, &voucher);
+            result = az_token_sale.buy_with_voucher(
+                MOCK_SALE_ID,
+                voucher.clone(),
+                other_sale_signature,
+                None,
+            );
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Invalid signature".to_string()
+                ))
+            );
+            // == when voucher is for this sale
+            // == * it goes on to buy
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), signature, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Sold out".to_string()
+                ))
+            );
+            // = when KYC signer has been rotated
+            // = * it rejects vouchers signed by the previous signer
+            let (new_kyc_signer, _) = sign_voucher([8; 32], MOCK_SALE_ID, &voucher);
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some(new_kyc_signer))
+                .unwrap();
+            voucher.nonce += 1;
+            let (_, signature) = sign_voucher([7; 32], MOCK_SALE_ID, &voucher);
+            result = az_token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), signature, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Invalid signature".to_string()
+                ))
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS BUY TRANSFERS out_token
+        }
+
+        #[ink::test]
+        fn test_cancel() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.cancel(sale_id);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has been finalized or cancelled
+            // = * it raises an error
+            for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.cancel(sale_id);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::InvalidTransition(
+                        status,
+                        SaleStatus::Cancelled
+                    ))
+                );
+            }
+            // = when sale is pending, active, paused or ended
+            // = * it cancels the sale
+            for status in [
+                SaleStatus::Pending,
+                SaleStatus::Active,
+                SaleStatus::Paused,
+                SaleStatus::Ended,
+            ] {
+                set_status(&mut az_token_sale, sale_id, status);
+                az_token_sale.cancel(sale_id).unwrap();
+                assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Cancelled));
+            }
+            // = when AZERO has been held back for liquidity
+            // = * it moves it into escrow for refunds
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Active);
+            az_token_sale.liquidity.insert(
+                sale_id,
+                &Liquidity {
+                    azero_amount: MOCK_IN_UNIT,
+                    lp_amount: 0,
+                },
+            );
+            az_token_sale.cancel(sale_id).unwrap();
+            assert_eq!(az_token_sale.liquidity(sale_id), Liquidity::default());
+            assert_eq!(az_token_sale.sale(sale_id).unwrap().proceeds, MOCK_IN_UNIT);
+            // when called by guardian
+            // * it cancels the sale
+            az_token_sale.set_guardian(Some(accounts.charlie)).unwrap();
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Active);
+            set_caller::<DefaultEnvironment>(accounts.charlie);
+            az_token_sale.cancel(sale_id).unwrap();
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Cancelled));
+        }
+
+        #[ink::test]
+        fn test_create_sale() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.create_sale(
+                accounts.bob,
+                accounts.eve,
+                MOCK_IN_UNIT,
+                MOCK_OUT_UNIT,
+                0,
+                None,
+            );
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when a unit is zero
+            // = * it raises an error
+            result =
+                az_token_sale.create_sale(accounts.bob, accounts.eve, 0, MOCK_OUT_UNIT, 0, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Units must be positive".to_string()
+                ))
+            );
+            result =
+                az_token_sale.create_sale(accounts.bob, accounts.eve, MOCK_IN_UNIT, 0, 0, None);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Units must be positive".to_string()
+                ))
+            );
+            // = when end is not after start
+            // = * it raises an error
+            result = az_token_sale.create_sale(
+                accounts.bob,
+                accounts.eve,
+                MOCK_IN_UNIT,
+                MOCK_OUT_UNIT,
+                10,
+                Some(10),
+            );
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "End must be after start".to_string()
+                ))
+            );
+            // = when valid
+            // = * it adds the sale under the next id
+            result = az_token_sale.create_sale(
+                accounts.bob,
+                accounts.django,
+                MOCK_IN_UNIT * 2,
+                MOCK_OUT_UNIT * 3,
+                10,
+                Some(20),
+            );
+            assert_eq!(result, Ok(MOCK_SALE_ID + 1));
+            assert_eq!(az_token_sale.sales_count(), 2);
+            assert_eq!(
+                az_token_sale.sale(MOCK_SALE_ID + 1),
+                Ok(Sale {
+                    admin: accounts.bob,
+                    out_token: accounts.django,
+                    in_unit: MOCK_IN_UNIT * 2,
+                    out_unit: MOCK_OUT_UNIT * 3,
+                    inventory: 0,
+                    proceeds: 0,
+                    released: 0,
+                    sold: 0,
+                    start: 10,
+                    end: Some(20),
+                    mint_on_purchase: false
+                })
+            );
+            // = * the new sale's admin manages it, not the other sales
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID + 1, Some([2; 20]))
+                .unwrap();
+            assert_eq!(
+                az_token_sale.set_kyc_signer(MOCK_SALE_ID, Some([2; 20])),
+                Err(AZTokenSaleError::Unauthorised)
+            );
+            assert_eq!(az_token_sale.kyc_signer(MOCK_SALE_ID), None);
+        }
+
+        #[ink::test]
+        fn test_end_allowlist_phase() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.end_allowlist_phase(MOCK_SALE_ID);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale is not allowlist-only
+            // = * it raises an error
+            result = az_token_sale.end_allowlist_phase(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Sale is not allowlist-only".to_string()
+                ))
+            );
+            // = when sale is allowlist-only
+            // = * it opens the sale to the public
+            az_token_sale
+                .set_allowlist_phase(MOCK_SALE_ID, Some(MOCK_END))
+                .unwrap();
+            az_token_sale.end_allowlist_phase(MOCK_SALE_ID).unwrap();
+            assert!(!az_token_sale.allowlist_only(MOCK_SALE_ID));
+            assert_eq!(az_token_sale.allowlist_public_at(MOCK_SALE_ID), None);
+        }
+
+        #[ink::test]
+        fn test_finalize() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.finalize(sale_id);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale is pending, finalized or cancelled
+            // = * it raises an error
+            for status in [
+                SaleStatus::Pending,
+                SaleStatus::Finalized,
+                SaleStatus::Cancelled,
+            ] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.finalize(sale_id);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::InvalidTransition(
+                        status,
+                        SaleStatus::Finalized
+                    ))
+                );
+            }
+            // = when sale is active, paused or ended
+            // == when liquidity is not configured
+            // == * it finalizes the sale
+            for status in [SaleStatus::Active, SaleStatus::Paused, SaleStatus::Ended] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.finalize(sale_id);
+                assert_eq!(result, Ok(Liquidity::default()));
+                assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Finalized));
+            }
+            // == when liquidity is configured
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Active);
+            az_token_sale
+                .set_liquidity_config(sale_id, mock_liquidity_config(&accounts))
+                .unwrap();
+            // === when no AZERO has been held back for liquidity
+            // === * it finalizes the sale without adding liquidity
+            result = az_token_sale.finalize(sale_id);
+            assert_eq!(result, Ok(Liquidity::default()));
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Finalized));
+            // === when AZERO has been held back for liquidity
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS ROUTER SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_invalidate_voucher_nonce() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.invalidate_voucher_nonce(MOCK_SALE_ID, accounts.bob, 5);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it marks the nonce as used
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .invalidate_voucher_nonce(MOCK_SALE_ID, accounts.bob, 5)
+                .unwrap();
+            assert!(az_token_sale.voucher_nonce_used(MOCK_SALE_ID, accounts.bob, 5));
+            assert!(!az_token_sale.voucher_nonce_used(MOCK_SALE_ID, accounts.bob, 4));
+            assert!(!az_token_sale.voucher_nonce_used(MOCK_SALE_ID, accounts.charlie, 5));
+        }
+
+        #[ink::test]
+        fn test_migrate() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.migrate();
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // = when storage is already at the current version
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            result = az_token_sale.migrate();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Storage is already up to date".to_string()
+                ))
+            );
+            // = when storage is at version 1
+            ink::env::set_contract_storage(
+                &CONFIG_STORAGE_KEY,
+                &ConfigV1 {
+                    version: 1,
+                    admin: accounts.alice,
+                    out_token: accounts.django,
+                    in_unit: MOCK_IN_UNIT * 2,
+                    out_unit: MOCK_OUT_UNIT * 3,
+                },
+            );
+            ink::env::set_contract_storage(&V1_MINT_ON_PURCHASE_KEY, &true);
+            ink::env::set_contract_storage(&V1_KYC_SIGNER_KEY, &Some([2u8; 20]));
+            ink::env::set_contract_storage(&V1_BONUS_BRACKETS_KEY, &mock_bonus_brackets());
+            ink::env::set_contract_storage(
+                &V1_LIQUIDITY_KEY,
+                &LiquidityV1 {
+                    azero_amount: 0,
+                    lp_amount: MOCK_IN_UNIT,
+                    finalized: true,
+                },
+            );
+            // = * it moves the single sale to sale 0
+            result = az_token_sale.migrate();
+            assert_eq!(result, Ok(STORAGE_VERSION));
+            assert_eq!(az_token_sale.config().version, STORAGE_VERSION);
+            assert_eq!(az_token_sale.config().admin, accounts.alice);
+            assert_eq!(
+                az_token_sale.sale(MOCK_SALE_ID),
+                Ok(Sale {
+                    admin: accounts.alice,
+                    out_token: accounts.django,
+                    in_unit: MOCK_IN_UNIT * 2,
+                    out_unit: MOCK_OUT_UNIT * 3,
+                    inventory: 0,
+                    proceeds: 0,
+                    released: 0,
+                    sold: 0,
+                    start: 0,
+                    end: None,
+                    mint_on_purchase: true
+                })
+            );
+            assert_eq!(az_token_sale.sales_count(), 1);
+            assert_eq!(az_token_sale.kyc_signer(MOCK_SALE_ID), Some([2; 20]));
+            assert_eq!(
+                az_token_sale.bonus_brackets(MOCK_SALE_ID),
+                mock_bonus_brackets()
+            );
+            assert_eq!(
+                az_token_sale.liquidity(MOCK_SALE_ID),
+                Liquidity {
+                    azero_amount: 0,
+                    lp_amount: MOCK_IN_UNIT
+                }
+            );
+            assert_eq!(
+                az_token_sale.status(MOCK_SALE_ID),
+                Ok(SaleStatus::Finalized)
+            );
+            // = * it clears the v1 values
+            assert_eq!(
+                ink::env::get_contract_storage::<u32, bool>(&V1_MINT_ON_PURCHASE_KEY),
+                Ok(None)
+            );
+            assert_eq!(
+                ink::env::get_contract_storage::<u32, Option<[u8; 20]>>(&V1_KYC_SIGNER_KEY),
+                Ok(None)
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS BUTTON SMART CONTRACT FOR INVENTORY
+        }
+
+        #[ink::test]
+        fn test_pause() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.pause(sale_id);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale is paused, ended, finalized or cancelled
+            // = * it raises an error
+            for status in [
+                SaleStatus::Paused,
+                SaleStatus::Ended,
+                SaleStatus::Finalized,
+                SaleStatus::Cancelled,
+            ] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.pause(sale_id);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::InvalidTransition(
+                        status,
+                        SaleStatus::Paused
+                    ))
+                );
+            }
+            // = when sale is pending or active
+            // = * it pauses the sale
+            for status in [SaleStatus::Pending, SaleStatus::Active] {
+                set_status(&mut az_token_sale, sale_id, status);
+                az_token_sale.pause(sale_id).unwrap();
+                assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Paused));
+            }
+        }
+
+        #[ink::test]
+        fn test_refund() {
+            let (accounts, mut az_token_sale) = init();
+            // when sale has not been cancelled
+            // * it raises an error
+            let mut result = az_token_sale.refund(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Active))
+            );
+            // when sale has been cancelled
+            az_token_sale.cancel(MOCK_SALE_ID).unwrap();
+            // = when caller has not contributed
+            // = * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            result = az_token_sale.refund(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Nothing to refund".to_string()
+                ))
+            );
+            // = when caller has contributed
+            // == when caller has no out_token to return
+            // == * it refunds the AZERO from escrow
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.proceeds = MOCK_IN_UNIT;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            az_token_sale.contributions.insert(
+                (MOCK_SALE_ID, accounts.bob),
+                &Contribution {
+                    in_amount: MOCK_IN_UNIT,
+                    out_amount: 0,
+                },
+            );
+            ink::env::test::set_account_balance::<DefaultEnvironment>(
+                ink::env::account_id::<DefaultEnvironment>(),
+                MOCK_IN_UNIT,
+            );
+            let bob_balance: Balance =
+                ink::env::test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+            result = az_token_sale.refund(MOCK_SALE_ID);
+            assert_eq!(result, Ok(MOCK_IN_UNIT));
+            assert_eq!(
+                ink::env::test::get_account_balance::<DefaultEnvironment>(accounts.bob),
+                Ok(bob_balance + MOCK_IN_UNIT)
+            );
+            assert_eq!(az_token_sale.sale(MOCK_SALE_ID).unwrap().proceeds, 0);
+            // == * it can only be claimed once
+            assert_eq!(
+                az_token_sale.contribution(MOCK_SALE_ID, accounts.bob),
+                Contribution::default()
+            );
+            // == when caller has out_token to return
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS BUTTON SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_release_lp_tokens() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.release_lp_tokens(MOCK_SALE_ID);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when liquidity is not configured
+            // = * it raises an error
+            result = az_token_sale.release_lp_tokens(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Liquidity is not configured".to_string()
+                ))
+            );
+            // = when liquidity is configured
+            let liquidity_config = mock_liquidity_config(&accounts);
+            az_token_sale
+                .set_liquidity_config(MOCK_SALE_ID, liquidity_config.clone())
+                .unwrap();
+            // == when sale has not been finalized
+            // == * it raises an error
+            result = az_token_sale.release_lp_tokens(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Active))
+            );
+            // == when sale has been finalized
+            az_token_sale.finalize(MOCK_SALE_ID).unwrap();
+            // === when LP tokens are still locked
+            // === * it raises an error
+            result = az_token_sale.release_lp_tokens(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "LP tokens are locked".to_string()
+                ))
+            );
+            // === when LP tokens are unlocked
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(
+                liquidity_config.lp_unlock_timestamp,
+            );
+            // ==== when there are no LP tokens
+            // ==== * it raises an error
+            result = az_token_sale.release_lp_tokens(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "No LP tokens to release".to_string()
+                ))
+            );
+            // ==== when there are LP tokens
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS LP TOKEN SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_release_tranche() {
+            let (accounts, mut az_token_sale) = init();
+            ink::env::test::set_account_balance::<DefaultEnvironment>(
+                ink::env::account_id::<DefaultEnvironment>(),
+                MOCK_IN_UNIT * 2,
+            );
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.release_tranche(MOCK_SALE_ID, 0);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has not been finalized
+            // = * it raises an error
+            result = az_token_sale.release_tranche(MOCK_SALE_ID, 0);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Active))
+            );
+            // = when sale has been finalized
+            // == when sale has no tranches
+            az_token_sale
+                .status
+                .insert(MOCK_SALE_ID, &SaleStatus::Finalized);
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.proceeds = MOCK_IN_UNIT;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            // === when releasing any tranche but the first
+            // === * it raises an error
+            result = az_token_sale.release_tranche(MOCK_SALE_ID, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::NotFound("Tranche".to_string()))
+            );
+            // === when releasing the first tranche
+            // === * it sends all of the proceeds to the admin
+            let alice_balance: Balance =
+                ink::env::test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            result = az_token_sale.release_tranche(MOCK_SALE_ID, 0);
+            assert_eq!(result, Ok(MOCK_IN_UNIT));
+            assert_eq!(
+                ink::env::test::get_account_balance::<DefaultEnvironment>(accounts.alice),
+                Ok(alice_balance + MOCK_IN_UNIT)
+            );
+            assert_eq!(az_token_sale.locked(MOCK_SALE_ID), Ok(0));
+            assert_eq!(az_token_sale.released(MOCK_SALE_ID), Ok(MOCK_IN_UNIT));
+            assert_eq!(
+                az_token_sale.tranche_released(MOCK_SALE_ID, 0),
+                Some(MOCK_IN_UNIT)
+            );
+            // === when the tranche has already been released
+            // === * it raises an error
+            result = az_token_sale.release_tranche(MOCK_SALE_ID, 0);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche already released".to_string()
+                ))
+            );
+            // == when sale has tranches
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Pending);
+            az_token_sale
+                .set_tranches(sale_id, mock_tranches())
+                .unwrap();
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Finalized);
+            let mut sale: Sale = az_token_sale.sale(sale_id).unwrap();
+            sale.proceeds = 7;
+            sale.sold = 100;
+            az_token_sale.sales.insert(sale_id, &sale);
+            // === when the unlock timestamp has not been reached
+            // === * it raises an error
+            result = az_token_sale.release_tranche(sale_id, 0);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche is locked".to_string()
+                ))
+            );
+            // === when no more than half of the out_token sold has voted for it
+            // === * it raises an error
+            az_token_sale.tranche_votes.insert((sale_id, 1), &50);
+            result = az_token_sale.release_tranche(sale_id, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche is locked".to_string()
+                ))
+            );
+            // === when more than half of the out_token sold has voted for it
+            // === * it releases its share of the proceeds, rounded down
+            az_token_sale.tranche_votes.insert((sale_id, 1), &51);
+            result = az_token_sale.release_tranche(sale_id, 1);
+            assert_eq!(result, Ok(4));
+            assert_eq!(az_token_sale.locked(sale_id), Ok(3));
+            assert_eq!(az_token_sale.released(sale_id), Ok(4));
+            // === when the unlock timestamp has been reached
+            // === * it releases the rest of the proceeds as the last tranche
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_END + 10);
+            result = az_token_sale.release_tranche(sale_id, 0);
+            assert_eq!(result, Ok(3));
+            assert_eq!(az_token_sale.locked(sale_id), Ok(0));
+            assert_eq!(az_token_sale.released(sale_id), Ok(7));
+        }
+
+        #[ink::test]
+        fn test_remove_from_allowlist() {
+            let (accounts, mut az_token_sale) = init();
+            az_token_sale
+                .add_to_allowlist(
+                    MOCK_SALE_ID,
+                    vec![
+                        (accounts.bob, MOCK_IN_UNIT),
+                        (accounts.charlie, MOCK_IN_UNIT),
+                    ],
+                )
+                .unwrap();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.remove_from_allowlist(MOCK_SALE_ID, vec![accounts.bob]);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it removes the allocations
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .remove_from_allowlist(MOCK_SALE_ID, vec![accounts.bob, accounts.django])
+                .unwrap();
+            assert_eq!(az_token_sale.allocation(MOCK_SALE_ID, accounts.bob), None);
+            assert_eq!(
+                az_token_sale.allocation(MOCK_SALE_ID, accounts.charlie),
+                Some(MOCK_IN_UNIT)
+            );
+        }
+
+        #[ink::test]
+        fn test_remove_from_blocklist() {
+            let (accounts, mut az_token_sale) = init();
+            az_token_sale
+                .add_to_blocklist(vec![accounts.bob, accounts.charlie])
+                .unwrap();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.remove_from_blocklist(vec![accounts.bob]);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it unblocks the accounts
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .remove_from_blocklist(vec![accounts.bob])
+                .unwrap();
+            assert!(!az_token_sale.blocklisted(accounts.bob));
+            assert!(az_token_sale.blocklisted(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn test_set_allowlist_phase() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_allowlist_phase(MOCK_SALE_ID, None);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = without public_at
+            // = * it makes the sale allowlist-only until the admin ends the phase
+            az_token_sale
+                .set_allowlist_phase(MOCK_SALE_ID, None)
+                .unwrap();
+            assert!(az_token_sale.allowlist_only(MOCK_SALE_ID));
+            assert_eq!(az_token_sale.allowlist_public_at(MOCK_SALE_ID), None);
+            // = with public_at
+            // = * it makes the sale allowlist-only until public_at
+            az_token_sale
+                .set_allowlist_phase(MOCK_SALE_ID, Some(MOCK_END))
+                .unwrap();
+            assert!(az_token_sale.allowlist_only(MOCK_SALE_ID));
+            assert_eq!(
+                az_token_sale.allowlist_public_at(MOCK_SALE_ID),
+                Some(MOCK_END)
+            );
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_END);
+            assert!(!az_token_sale.allowlist_only(MOCK_SALE_ID));
+        }
+
+        #[ink::test]
+        fn test_set_anti_bot_config() {
+            let (accounts, mut az_token_sale) = init();
+            let anti_bot_config = AntiBotConfig {
+                cooldown_blocks: 3,
+                max_out_amount_per_block: Some(MOCK_OUT_UNIT * 100),
+                no_contract_callers: true,
+            };
+            // * it defaults to no protections
+            assert_eq!(
+                az_token_sale.anti_bot_config(MOCK_SALE_ID),
+                AntiBotConfig::default()
+            );
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_anti_bot_config(MOCK_SALE_ID, anti_bot_config.clone());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it sets the anti-bot config
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .set_anti_bot_config(MOCK_SALE_ID, anti_bot_config.clone())
+                .unwrap();
+            assert_eq!(az_token_sale.anti_bot_config(MOCK_SALE_ID), anti_bot_config);
+        }
+
+        #[ink::test]
+        fn test_set_bonus_brackets() {
+            let (accounts, mut az_token_sale) = init();
+            let mut bonus_brackets = vec![
+                BonusBracket {
+                    min_in_amount: MOCK_IN_UNIT * 10,
+                    bonus_percentage: 5,
+                },
+                BonusBracket {
+                    min_in_amount: MOCK_IN_UNIT * 100,
+                    bonus_percentage: 10,
+                },
+            ];
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.set_bonus_brackets(MOCK_SALE_ID, bonus_brackets.clone());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when there are too many brackets
+            // = * it raises an error
+            result = az_token_sale.set_bonus_brackets(
+                MOCK_SALE_ID,
+                vec![bonus_brackets[0].clone(); MAX_BONUS_BRACKETS + 1],
+            );
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Too many bonus brackets".to_string()
+                ))
+            );
+            // = when brackets are not in ascending order of min_in_amount
+            // = * it raises an error
+            result = az_token_sale.set_bonus_brackets(
+                MOCK_SALE_ID,
+                vec![bonus_brackets[1].clone(), bonus_brackets[0].clone()],
+            );
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus brackets must be in ascending order of min_in_amount".to_string()
+                ))
+            );
+            // = when a bonus percentage is over 100
+            // = * it raises an error
+            bonus_brackets[1].bonus_percentage = 101;
+            result = az_token_sale.set_bonus_brackets(MOCK_SALE_ID, bonus_brackets.clone());
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus percentage must be less than or equal to 100".to_string()
+                ))
+            );
+            // = when a bonus percentage is lower than the one before it
+            // = * it raises an error
+            bonus_brackets[1].bonus_percentage = 4;
+            result = az_token_sale.set_bonus_brackets(MOCK_SALE_ID, bonus_brackets.clone());
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Bonus percentages must not decrease".to_string()
+                ))
+            );
+            // = when brackets are valid
+            // = * it sets the bonus brackets
+            bonus_brackets[1].bonus_percentage = 10;
+            az_token_sale
+                .set_bonus_brackets(MOCK_SALE_ID, bonus_brackets.clone())
+                .unwrap();
+            assert_eq!(az_token_sale.bonus_brackets(MOCK_SALE_ID), bonus_brackets);
+        }
+
+        #[ink::test]
+        fn test_set_code() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_code([0; 32].into());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // SET CODE HASH IS NOT SUPPORTED OFF-CHAIN, SEE INTEGRATION TEST
+        }
+
+        #[ink::test]
+        fn test_set_liquidity_config() {
+            let (accounts, mut az_token_sale) = init();
+            let mut liquidity_config = mock_liquidity_config(&accounts);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result =
+                az_token_sale.set_liquidity_config(MOCK_SALE_ID, liquidity_config.clone());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when AZERO percentage is over 100
+            // = * it raises an error
+            liquidity_config.azero_percentage = 101;
+            result = az_token_sale.set_liquidity_config(MOCK_SALE_ID, liquidity_config.clone());
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "AZERO percentage must be less than or equal to 100".to_string()
+                ))
+            );
+            // = when AZERO percentage is 100 or less
+            // = * it sets the liquidity config
+            liquidity_config.azero_percentage = 100;
+            az_token_sale
+                .set_liquidity_config(MOCK_SALE_ID, liquidity_config.clone())
+                .unwrap();
+            assert_eq!(
+                az_token_sale.liquidity_config(MOCK_SALE_ID),
+                Some(liquidity_config.clone())
+            );
+            // = when out_token has been sold
+            // = * it raises an error
+            let mut sale: Sale = az_token_sale.sale(MOCK_SALE_ID).unwrap();
+            sale.sold = MOCK_OUT_UNIT;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            result = az_token_sale.set_liquidity_config(MOCK_SALE_ID, liquidity_config.clone());
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Liquidity config can only be set before the first purchase".to_string()
+                ))
+            );
+            sale.sold = 0;
+            az_token_sale.sales.insert(MOCK_SALE_ID, &sale);
+            // = when sale has been finalized
+            // = * it raises an error
+            az_token_sale.finalize(MOCK_SALE_ID).unwrap();
+            result = az_token_sale.set_liquidity_config(MOCK_SALE_ID, liquidity_config);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Finalized))
+            );
+        }
+
+        #[ink::test]
+        fn test_set_guardian() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by a sale admin that is not the contract admin
+            // * it raises an error
+            az_token_sale
+                .create_sale(
+                    accounts.bob,
+                    accounts.eve,
+                    MOCK_IN_UNIT,
+                    MOCK_OUT_UNIT,
+                    0,
+                    None,
+                )
+                .unwrap();
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_guardian(Some(accounts.charlie));
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by contract admin
+            // * it sets the guardian
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale.set_guardian(Some(accounts.charlie)).unwrap();
+            assert_eq!(az_token_sale.guardian(), Some(accounts.charlie));
+            az_token_sale.set_guardian(None).unwrap();
+            assert_eq!(az_token_sale.guardian(), None);
+        }
+
+        #[ink::test]
+        fn test_set_kyc_signer() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_kyc_signer(MOCK_SALE_ID, Some([2; 20]));
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it rotates the signer
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some([2; 20]))
+                .unwrap();
+            assert_eq!(az_token_sale.kyc_signer(MOCK_SALE_ID), Some([2; 20]));
+            az_token_sale
+                .set_kyc_signer(MOCK_SALE_ID, Some([3; 20]))
+                .unwrap();
+            assert_eq!(az_token_sale.kyc_signer(MOCK_SALE_ID), Some([3; 20]));
+            // = when set to None
+            // = * it disables vouchers
+            az_token_sale.set_kyc_signer(MOCK_SALE_ID, None).unwrap();
+            assert_eq!(az_token_sale.kyc_signer(MOCK_SALE_ID), None);
+        }
+
+        #[ink::test]
+        fn test_set_metadata() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.set_metadata(sale_id, mock_metadata());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has started
+            // = * it raises an error
+            for status in [
+                SaleStatus::Paused,
+                SaleStatus::Active,
+                SaleStatus::Ended,
+                SaleStatus::Finalized,
+                SaleStatus::Cancelled,
+            ] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.set_metadata(sale_id, mock_metadata());
+                assert_eq!(result, Err(AZTokenSaleError::InvalidSaleStatus(status)));
+            }
+            // = when sale is pending
+            // = * it sets the metadata
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Pending);
+            az_token_sale
+                .set_metadata(sale_id, mock_metadata())
+                .unwrap();
+            assert_eq!(az_token_sale.metadata(sale_id), Some(mock_metadata()));
+        }
+
+        #[ink::test]
+        fn test_set_mint_on_purchase() {
+            let (accounts, mut az_token_sale) = init();
+            // * it defaults to selling from inventory
+            assert!(!az_token_sale.sale(MOCK_SALE_ID).unwrap().mint_on_purchase);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_mint_on_purchase(MOCK_SALE_ID, true);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it sets mint on purchase
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .set_mint_on_purchase(MOCK_SALE_ID, true)
+                .unwrap();
+            assert!(az_token_sale.sale(MOCK_SALE_ID).unwrap().mint_on_purchase);
+        }
+
+        #[ink::test]
+        fn test_set_price_oracle() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_price_oracle(Some(accounts.frank), 60_000);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it sets the price oracle and max price age
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .set_price_oracle(Some(accounts.frank), 60_000)
+                .unwrap();
+            let mut config: Config = az_token_sale.config();
+            assert_eq!(config.price_oracle, Some(accounts.frank));
+            assert_eq!(config.max_price_age, 60_000);
+            // * it can unset the price oracle
+            az_token_sale.set_price_oracle(None, 0).unwrap();
+            config = az_token_sale.config();
+            assert_eq!(config.price_oracle, None);
+            assert_eq!(config.max_price_age, 0);
+        }
+
+        #[ink::test]
+        fn test_set_screening_oracle() {
+            let (accounts, mut az_token_sale) = init();
+            // * it defaults to no screening oracle
+            assert_eq!(az_token_sale.screening_oracle(), None);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let result = az_token_sale.set_screening_oracle(Some(accounts.frank));
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // * it sets and unsets the screening oracle
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            az_token_sale
+                .set_screening_oracle(Some(accounts.frank))
+                .unwrap();
+            assert_eq!(az_token_sale.screening_oracle(), Some(accounts.frank));
+            az_token_sale.set_screening_oracle(None).unwrap();
+            assert_eq!(az_token_sale.screening_oracle(), None);
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS BUY CALLS THE SCREENING ORACLE SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_set_tranches() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.set_tranches(sale_id, mock_tranches());
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has been finalized or cancelled
+            // = * it raises an error
+            for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.set_tranches(sale_id, mock_tranches());
+                assert_eq!(result, Err(AZTokenSaleError::InvalidSaleStatus(status)));
+            }
+            // = when out_token has been sold
+            // = * it raises an error
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Active);
+            let mut sale: Sale = az_token_sale.sale(sale_id).unwrap();
+            sale.sold = MOCK_OUT_UNIT;
+            az_token_sale.sales.insert(sale_id, &sale);
+            result = az_token_sale.set_tranches(sale_id, mock_tranches());
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranches can only be set before the first purchase".to_string()
+                ))
+            );
+            sale.sold = 0;
+            az_token_sale.sales.insert(sale_id, &sale);
+            // = when nothing has been sold
+            // == when there are too many tranches
+            // == * it raises an error
+            let tranche = Tranche {
+                percentage: 9,
+                unlock: TrancheUnlock::Vote,
+            };
+            result = az_token_sale.set_tranches(sale_id, vec![tranche; MAX_TRANCHES + 1]);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Too many tranches".to_string()
+                ))
+            );
+            // == when a tranche has no percentage
+            // == * it raises an error
+            let mut tranches: Vec<Tranche> = mock_tranches();
+            tranches.push(Tranche {
+                percentage: 0,
+                unlock: TrancheUnlock::Vote,
+            });
+            result = az_token_sale.set_tranches(sale_id, tranches);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche percentage must be positive".to_string()
+                ))
+            );
+            // == when percentages do not add up to 100
+            // == * it raises an error
+            let mut tranches: Vec<Tranche> = mock_tranches();
+            tranches[1].percentage = 61;
+            result = az_token_sale.set_tranches(sale_id, tranches);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche percentages must add up to 100".to_string()
+                ))
+            );
+            // == when tranches are valid
+            // == * it sets the tranches
+            az_token_sale
+                .set_tranches(sale_id, mock_tranches())
+                .unwrap();
+            assert_eq!(az_token_sale.tranches(sale_id), mock_tranches());
+            // == when tranches are empty
+            // == * it removes the tranches
+            az_token_sale.set_tranches(sale_id, vec![]).unwrap();
+            assert_eq!(az_token_sale.tranches(sale_id), vec![]);
+        }
+
+        #[ink::test]
+        fn test_set_usd_price() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            let usd_price: UsdPrice = UsdPrice {
+                usd_amount: 1_000_000,
+                out_amount: 2,
+            };
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.set_usd_price(sale_id, Some(usd_price.clone()));
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale has been finalized or cancelled
+            // = * it raises an error
+            for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.set_usd_price(sale_id, Some(usd_price.clone()));
+                assert_eq!(result, Err(AZTokenSaleError::InvalidSaleStatus(status)));
+            }
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Pending);
+            // = when an amount is zero
+            // = * it raises an error
+            for (usd_amount, out_amount) in [(0, 2), (1_000_000, 0)] {
+                result = az_token_sale.set_usd_price(
+                    sale_id,
+                    Some(UsdPrice {
+                        usd_amount,
+                        out_amount,
+                    }),
+                );
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::UnprocessableEntity(
+                        "USD price amounts must be positive".to_string()
+                    ))
+                );
+            }
+            // = when price oracle is not set
+            // = * it raises an error
+            result = az_token_sale.set_usd_price(sale_id, Some(usd_price.clone()));
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Price oracle is not set".to_string()
+                ))
+            );
+            // = when price oracle is set
+            // = * it sets the USD price
+            az_token_sale
+                .set_price_oracle(Some(accounts.frank), 60_000)
+                .unwrap();
+            az_token_sale
+                .set_usd_price(sale_id, Some(usd_price.clone()))
+                .unwrap();
+            assert_eq!(az_token_sale.usd_price(sale_id), Some(usd_price));
+            // == when price oracle is unset afterwards
+            // == * buying raises an error
+            az_token_sale.set_price_oracle(None, 0).unwrap();
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Active);
+            assert_eq!(
+                az_token_sale.quote(sale_id, MOCK_IN_UNIT, None),
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Price oracle is not set".to_string()
+                ))
+            );
+            // = when unsetting the USD price
+            // = * it removes the USD price
+            az_token_sale.set_usd_price(sale_id, None).unwrap();
+            assert_eq!(az_token_sale.usd_price(sale_id), None);
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS BUY CALLS THE PRICE ORACLE SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_unpause() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.unpause(sale_id);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale is not paused
+            // = * it raises an error
+            for status in [
+                SaleStatus::Pending,
+                SaleStatus::Active,
+                SaleStatus::Ended,
+                SaleStatus::Finalized,
+                SaleStatus::Cancelled,
+            ] {
+                set_status(&mut az_token_sale, sale_id, status);
+                result = az_token_sale.unpause(sale_id);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::InvalidTransition(
+                        status,
+                        SaleStatus::Active
+                    ))
+                );
+            }
+            // = when sale is paused
+            // = * it hands the status back to the window
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Paused);
+            az_token_sale.unpause(sale_id).unwrap();
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Active));
+            az_token_sale.pause(sale_id).unwrap();
+            ink::env::test::set_block_timestamp::<DefaultEnvironment>(MOCK_END);
+            az_token_sale.unpause(sale_id).unwrap();
+            assert_eq!(az_token_sale.status(sale_id), Ok(SaleStatus::Ended));
+        }
+
+        #[ink::test]
+        fn test_vote_tranche() {
+            let (accounts, mut az_token_sale) = init();
+            let sale_id: u32 = create_windowed_sale(&accounts, &mut az_token_sale);
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Pending);
+            az_token_sale
+                .set_tranches(sale_id, mock_tranches())
+                .unwrap();
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            // when sale has not been finalized
+            // * it raises an error
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Ended);
+            let mut result = az_token_sale.vote_tranche(sale_id, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Ended))
+            );
+            // when sale has been finalized
+            set_status(&mut az_token_sale, sale_id, SaleStatus::Finalized);
+            // = when tranche does not exist
+            // = * it raises an error
+            result = az_token_sale.vote_tranche(sale_id, 2);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::NotFound("Tranche".to_string()))
+            );
+            // = when tranche is not unlocked by vote
+            // = * it raises an error
+            result = az_token_sale.vote_tranche(sale_id, 0);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche is not unlocked by vote".to_string()
+                ))
+            );
+            // = when tranche is unlocked by vote
+            // == when tranche has been released
+            // == * it raises an error
+            az_token_sale.released_tranches.insert((sale_id, 1), &0);
+            result = az_token_sale.vote_tranche(sale_id, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche already released".to_string()
+                ))
+            );
+            az_token_sale.released_tranches.remove((sale_id, 1));
+            // == when caller has already voted
+            // == * it raises an error
+            az_token_sale
+                .tranche_voters
+                .insert((sale_id, 1, accounts.bob), &());
+            result = az_token_sale.vote_tranche(sale_id, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Already voted".to_string()
+                ))
+            );
+            az_token_sale
+                .tranche_voters
+                .remove((sale_id, 1, accounts.bob));
+            // == when caller did not buy from the sale
+            // == * it raises an error
+            result = az_token_sale.vote_tranche(sale_id, 1);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Only buyers can vote".to_string()
+                ))
+            );
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS THE OUT_TOKEN SMART CONTRACT
+        }
+
+        #[ink::test]
+        fn test_withdraw_inventory() {
+            let (accounts, mut az_token_sale) = init();
+            // when called by non-admin
+            // * it raises an error
+            set_caller::<DefaultEnvironment>(accounts.bob);
+            let mut result = az_token_sale.withdraw_inventory(MOCK_SALE_ID);
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            set_caller::<DefaultEnvironment>(accounts.alice);
+            // = when sale is still running
+            // = * it raises an error
+            result = az_token_sale.withdraw_inventory(MOCK_SALE_ID);
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Active))
+            );
+            // = when sale has been finalized or cancelled
+            // == when there is no inventory
+            // == * it raises an error
+            for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+                az_token_sale.status.insert(MOCK_SALE_ID, &status);
+                result = az_token_sale.withdraw_inventory(MOCK_SALE_ID);
+                assert_eq!(
+                    result,
+                    Err(AZTokenSaleError::UnprocessableEntity(
+                        "No inventory to withdraw".to_string()
+                    ))
+                );
+            }
+            // == when there is inventory
+            // REST WILL HAVE TO GO INTO INTEGRATION TEST AS IT CALLS BUTTON SMART CONTRACT
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use crate::az_token_sale::AZTokenSaleRef;
+        use crate::pricing::AZERO_UNIT;
+        use az_button::{
+            ButtonRef, FlashLender, FlashMintConfig, Permit, TransferFeeConfig, MINTER, PAUSER,
+        };
+        use az_mock_flash_borrower::MockFlashBorrowerRef;
+        use az_mock_price_oracle::MockPriceOracleRef;
+        use az_mock_router::MockRouterRef;
+        use az_mock_screening_oracle::MockScreeningOracleRef;
+        use az_token_sale_v2::{AZTokenSaleV2Ref, Config as ConfigV2, Sale as SaleV2};
+        use ink::env::hash::{CryptoHash, HashOutput, Keccak256};
+        use ink_e2e::build_message;
+        use ink_e2e::Keypair;
+        use openbrush::contracts::{
+            pausable::PausableError,
+            psp22::PSP22Error,
+            traits::{
+                access_control::accesscontrol_external::AccessControl, psp22::psp22_external::PSP22,
+            },
+        };
+        use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+        // === CONSTANTS ===
+        const MOCK_IN_UNIT: Balance = 250;
         const MOCK_OUT_UNIT: Balance = 5;
+        const MOCK_SALE_ID: u32 = 0;
         const TOKEN_BALANCE: Balance = 1_000_000_000_000_000_000;
 
-        // === TYPES ===
-        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+        // === TYPES ===
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        // === HELPERS ===
+        fn account_id(k: Keypair) -> AccountId {
+            AccountId::try_from(k.public_key().to_account_id().as_ref())
+                .expect("account keyring has a valid account id")
+        }
+
+        fn blake2x256(input: &[u8]) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            <Blake2x256 as CryptoHash>::hash(input, &mut output);
+            output
+        }
+
+        // ECDSA accounts are the blake2x256 hash of the compressed public key
+        fn ecdsa_account_id(secret_key: [u8; 32]) -> AccountId {
+            let public_key = PublicKey::from_secret_key(
+                &Secp256k1::new(),
+                &SecretKey::from_slice(&secret_key).unwrap(),
+            );
+            AccountId::from(blake2x256(&public_key.serialize()))
+        }
+
+        fn eth_address(secret_key: [u8; 32]) -> [u8; 20] {
+            let public_key = PublicKey::from_secret_key(
+                &Secp256k1::new(),
+                &SecretKey::from_slice(&secret_key).unwrap(),
+            );
+            let mut public_key_hash = <Keccak256 as HashOutput>::Type::default();
+            <Keccak256 as CryptoHash>::hash(
+                &public_key.serialize_uncompressed()[1..],
+                &mut public_key_hash,
+            );
+            let mut address = [0; 20];
+            address.copy_from_slice(&public_key_hash[12..]);
+            address
+        }
+
+        // Signs blake2x256 of the SCALE encoded message.
+        fn sign<T: scale::Encode>(secret_key: [u8; 32], message: &T) -> [u8; 65] {
+            let message_hash: [u8; 32] = blake2x256(&scale::Encode::encode(message));
+            let (recovery_id, compact) = Secp256k1::new()
+                .sign_ecdsa_recoverable(
+                    &Message::from_slice(&message_hash).unwrap(),
+                    &SecretKey::from_slice(&secret_key).unwrap(),
+                )
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        // === TEST HANDLES ===
+        #[ink_e2e::test]
+        async fn test_add_amount_for_sale(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+
+            // Instantiate to token
+            let to_token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("Button".to_string()),
+                Some("BTN".to_string()),
+                6,
+            );
+            let to_token_id: AccountId = client
+                .instantiate(
+                    "az_button",
+                    &ink_e2e::alice(),
+                    to_token_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("Reward token instantiate failed")
+                .account_id;
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, TOKEN_BALANCE));
+            // when called by non-admin
+            // * it raises an error
+            let result = client
+                .call_dry_run(&ink_e2e::charlie(), &add_amount_for_sale_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
+            // when called by admin
+            // = when amount added in is not divisible by out_unit
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, 1));
+            // # it raises an error
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &add_amount_for_sale_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Amount must be in multiples of out_unit".to_string()
+                ))
+            );
+            // = when amount added in is divisible by out_unit
+            // = * it transfers the token from admin to itself
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|to_token| to_token.increase_allowance(token_sale_id, u128::MAX));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(token_sale_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(MOCK_OUT_UNIT, balance);
+            // = * it adds the amount to the sale's inventory
+            let sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: Sale = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
+                .await
+                .return_value()
+                .unwrap();
+            assert_eq!(sale.inventory, MOCK_OUT_UNIT);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_add_amount_for_sale_with_permit(
+            mut client: ::ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let owner_secret_key: [u8; 32] = [9; 32];
+            let owner: AccountId = ecdsa_account_id(owner_secret_key);
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            // Give the ECDSA owner some tokens
+            let transfer_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.transfer(owner, MOCK_OUT_UNIT, vec![]));
+            client
+                .call(&ink_e2e::alice(), transfer_message, 0, None)
+                .await
+                .unwrap();
+
+            // when the owner has signed a permit for the sale contract
+            // * it approves and adds the owner's tokens in one call
+            let signature: [u8; 65] = sign(
+                owner_secret_key,
+                &(
+                    to_token_id,
+                    owner,
+                    token_sale_id,
+                    MOCK_OUT_UNIT,
+                    0_u64,
+                    Timestamp::MAX,
+                ),
+            );
+            let add_amount_for_sale_with_permit_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale_with_permit(
+                        MOCK_SALE_ID,
+                        owner,
+                        MOCK_OUT_UNIT,
+                        Timestamp::MAX,
+                        signature,
+                    )
+                });
+            let result = client
+                .call(
+                    &ink_e2e::alice(),
+                    add_amount_for_sale_with_permit_message,
+                    0,
+                    None,
+                )
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(MOCK_OUT_UNIT));
+            let balance_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| button.balance_of(owner));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 0);
+            let sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: Sale = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
+                .await
+                .return_value()
+                .unwrap();
+            assert_eq!(sale.inventory, MOCK_OUT_UNIT);
+            // * it uses up the owner's permit nonce
+            let permit_nonce_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| button.permit_nonce(owner));
+            let permit_nonce: u64 = client
+                .call_dry_run(&ink_e2e::alice(), &permit_nonce_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(permit_nonce, 1);
+            // when the permit is replayed
+            // * it raises an error
+            let add_amount_for_sale_with_permit_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale_with_permit(
+                        MOCK_SALE_ID,
+                        owner,
+                        MOCK_OUT_UNIT,
+                        Timestamp::MAX,
+                        signature,
+                    )
+                });
+            let result = client
+                .call_dry_run(
+                    &ink_e2e::alice(),
+                    &add_amount_for_sale_with_permit_message,
+                    0,
+                    None,
+                )
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::PSP22Error(PSP22Error::Custom(
+                    "Invalid signature".to_string()
+                )))
+            );
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_batch_transfer(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+            let charlie_account_id: AccountId = account_id(ink_e2e::charlie());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+            // Pause the token with only bob exempt
+            let grant_role_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.grant_role(PAUSER, Some(alice_account_id)));
+            client
+                .call(&ink_e2e::alice(), grant_role_message, 0, None)
+                .await
+                .unwrap();
+            let pause_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| button.pause());
+            client
+                .call(&ink_e2e::alice(), pause_message, 0, None)
+                .await
+                .unwrap();
+            let set_pause_exempt_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.set_pause_exempt(bob_account_id, true));
+            client
+                .call(&ink_e2e::alice(), set_pause_exempt_message, 0, None)
+                .await
+                .unwrap();
+
+            // when a transfer fails after earlier transfers went through
+            // * it raises an error
+            let batch_transfer_message = build_message::<ButtonRef>(to_token_id).call(|button| {
+                button.batch_transfer(vec![
+                    (bob_account_id, MOCK_OUT_UNIT),
+                    (charlie_account_id, MOCK_OUT_UNIT),
+                ])
+            });
+            let result = client
+                .call(&ink_e2e::alice(), batch_transfer_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Err(PSP22Error::from(PausableError::Paused)));
+            // * it reverts the earlier transfers
+            for (account, expected_balance) in [
+                (alice_account_id, TOKEN_BALANCE),
+                (bob_account_id, 0),
+                (charlie_account_id, 0),
+            ] {
+                let balance_message = build_message::<ButtonRef>(to_token_id)
+                    .call(|button| button.balance_of(account));
+                let balance: Balance = client
+                    .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                    .await
+                    .return_value();
+                assert_eq!(balance, expected_balance);
+            }
+            // when every transfer goes through
+            // * it makes all of them
+            let set_pause_exempt_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.set_pause_exempt(charlie_account_id, true));
+            client
+                .call(&ink_e2e::alice(), set_pause_exempt_message, 0, None)
+                .await
+                .unwrap();
+            let batch_transfer_message = build_message::<ButtonRef>(to_token_id).call(|button| {
+                button.batch_transfer(vec![
+                    (bob_account_id, MOCK_OUT_UNIT),
+                    (charlie_account_id, MOCK_OUT_UNIT),
+                ])
+            });
+            let result = client
+                .call(&ink_e2e::alice(), batch_transfer_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(()));
+            for (account, expected_balance) in [
+                (alice_account_id, TOKEN_BALANCE - MOCK_OUT_UNIT * 2),
+                (bob_account_id, MOCK_OUT_UNIT),
+                (charlie_account_id, MOCK_OUT_UNIT),
+            ] {
+                let balance_message = build_message::<ButtonRef>(to_token_id)
+                    .call(|button| button.balance_of(account));
+                let balance: Balance = client
+                    .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                    .await
+                    .return_value();
+                assert_eq!(balance, expected_balance);
+            }
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_buy(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // when in amount is zero
+            // * it raises an error
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &buy_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount must be positive".to_string()
+                ))
+            );
+
+            // when in amount is positive
+            // = when in amount is too small to buy any out_token
+            let result = client
+                .call_dry_run(
+                    &ink_e2e::alice(),
+                    &buy_message,
+                    MOCK_IN_UNIT / MOCK_OUT_UNIT - 1,
+                    None,
+                )
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "In amount is too small to buy any out_token".to_string()
+                ))
+            );
+            // = when in amount buys out_token
+            // == when there is enough stock to fill full order
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 2));
+            let add_amount_for_sale_result = client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(add_amount_for_sale_result, Ok(MOCK_OUT_UNIT * 2));
+
+            // == * it works, refunding any dust
+            let original_token_sale_azero_balance: Balance =
+                client.balance(token_sale_id).await.unwrap();
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let buy_result = client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT + 1, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(buy_result, Ok((MOCK_IN_UNIT, MOCK_OUT_UNIT)));
+
+            // == * it transfers the out amount to the caller
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, MOCK_OUT_UNIT);
+            // == * it escrows the in amount
+            assert_eq!(
+                client.balance(token_sale_id).await.unwrap(),
+                original_token_sale_azero_balance + MOCK_IN_UNIT
+            );
+
+            // == when there is only enough stock to partially fill order
+            // == * it works
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let buy_result = client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+                .await
+                .unwrap()
+                .dry_run
+                .exec_result
+                .result;
+            assert!(buy_result.is_ok());
+
+            // == * it transfers the available out amount to the caller
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, MOCK_OUT_UNIT * 2);
+
+            // == * it escrows the applicable in amount and refunds the rest to the buyer
+            assert_eq!(
+                client.balance(token_sale_id).await.unwrap(),
+                original_token_sale_azero_balance + MOCK_IN_UNIT * 2
+            );
+            let sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: Sale = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
+                .await
+                .return_value()
+                .unwrap();
+            assert_eq!(sale.proceeds, MOCK_IN_UNIT * 2);
+            assert_eq!(sale.inventory, 0);
+            // == * it records the buyer's contribution
+            let contribution_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.contribution(MOCK_SALE_ID, bob_account_id));
+            let contribution: Contribution = client
+                .call_dry_run(&ink_e2e::alice(), &contribution_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                contribution,
+                Contribution {
+                    in_amount: MOCK_IN_UNIT * 2,
+                    out_amount: MOCK_OUT_UNIT * 2
+                }
+            );
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_buy_with_mint_on_purchase(
+            mut client: ::ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+
+            // Instantiate token with nothing minted
+            let token_constructor = ButtonRef::new_with_initial_supply(
+                TOKEN_BALANCE,
+                0,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let set_mint_on_purchase_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_mint_on_purchase(MOCK_SALE_ID, true));
+            client
+                .call(&ink_e2e::alice(), set_mint_on_purchase_message, 0, None)
+                .await
+                .unwrap();
+
+            // when token sale is not a minter
+            // * it raises an error
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &buy_message, MOCK_IN_UNIT, None)
+                .await
+                .return_value();
+            assert!(matches!(result, Err(AZTokenSaleError::PSP22Error(_))));
+            // when token sale is a minter
+            // * it mints the out amount to the caller
+            let grant_role_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.grant_role(MINTER, Some(token_sale_id)));
+            client
+                .call(&ink_e2e::alice(), grant_role_message, 0, None)
+                .await
+                .unwrap();
+            client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+                .await
+                .unwrap();
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_OUT_UNIT * 2);
+            let total_supply_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| button.total_supply());
+            let total_supply: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &total_supply_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(total_supply, MOCK_OUT_UNIT * 2);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_buy_with_transfer_fee(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+            let treasury_account_id: AccountId = account_id(ink_e2e::django());
+
+            // Instantiate token with a 1% transfer fee
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+            let set_transfer_fee_config_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| {
+                    button.set_transfer_fee_config(Some(TransferFeeConfig {
+                        treasury: treasury_account_id,
+                        fee_basis_points: 100,
+                    }))
+                });
+            client
+                .call(&ink_e2e::alice(), set_transfer_fee_config_message, 0, None)
+                .await
+                .unwrap();
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // when adding amount for sale
+            // * it returns the amount received after the fee
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|to_token| to_token.increase_allowance(token_sale_id, u128::MAX));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 200)
+                });
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &add_amount_for_sale_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Ok(MOCK_OUT_UNIT * 198));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // when buying
+            // * it returns the out amount received after the fee
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &buy_message, MOCK_IN_UNIT * 100, None)
+                .await
+                .return_value();
+            assert_eq!(result, Ok((MOCK_IN_UNIT * 100, MOCK_OUT_UNIT * 99)));
+            client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 100, None)
+                .await
+                .unwrap();
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_OUT_UNIT * 99);
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(treasury_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_OUT_UNIT * 3);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_buy_with_voucher(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let voucher: Voucher = Voucher {
+                buyer: bob_account_id,
+                max_allocation: MOCK_IN_UNIT,
+                expiry: Timestamp::MAX,
+                nonce: 0,
+            };
+            let kyc_signer: [u8; 20] = eth_address([7; 32]);
+            let signature: [u8; 65] = sign([7; 32], &(token_sale_id, MOCK_SALE_ID, &voucher));
+            let set_kyc_signer_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_kyc_signer(MOCK_SALE_ID, Some(kyc_signer)));
+            client
+                .call(&ink_e2e::alice(), set_kyc_signer_message, 0, None)
+                .await
+                .unwrap();
+
+            // when voucher is signed by the KYC signer
+            // * it buys
+            let buy_with_voucher_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), signature, None)
+                });
+            let result = client
+                .call(
+                    &ink_e2e::bob(),
+                    buy_with_voucher_message,
+                    MOCK_IN_UNIT,
+                    None,
+                )
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok((MOCK_IN_UNIT, MOCK_OUT_UNIT)));
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_OUT_UNIT);
+            // * it uses up the nonce
+            let voucher_nonce_used_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.voucher_nonce_used(MOCK_SALE_ID, bob_account_id, voucher.nonce)
+                });
+            let nonce_used: bool = client
+                .call_dry_run(&ink_e2e::alice(), &voucher_nonce_used_message, 0, None)
+                .await
+                .return_value();
+            assert!(nonce_used);
+            // when voucher is replayed
+            // * it raises an error
+            let buy_with_voucher_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.buy_with_voucher(MOCK_SALE_ID, voucher.clone(), signature, None)
+                });
+            let result = client
+                .call_dry_run(
+                    &ink_e2e::bob(),
+                    &buy_with_voucher_message,
+                    MOCK_IN_UNIT,
+                    None,
+                )
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Voucher has already been used".to_string()
+                ))
+            );
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_finalize(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate LP token
+            let lp_token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS-AZERO LP".to_string()),
+                Some("LP".to_string()),
+                12,
+            );
+            let lp_token_id: AccountId = client
+                .instantiate(
+                    "az_button",
+                    &ink_e2e::alice(),
+                    lp_token_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("LP token instantiate failed")
+                .account_id;
+
+            // Instantiate router and give it the LP token supply
+            let router_constructor = MockRouterRef::new(lp_token_id);
+            let router_id: AccountId = client
+                .instantiate(
+                    "az_mock_router",
+                    &ink_e2e::alice(),
+                    router_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("Router instantiate failed")
+                .account_id;
+            let transfer_message = build_message::<ButtonRef>(lp_token_id)
+                .call(|button| button.transfer(router_id, TOKEN_BALANCE, vec![]));
+            client
+                .call(&ink_e2e::alice(), transfer_message, 0, None)
+                .await
+                .unwrap();
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 10));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 10)
+                });
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // Hold back half of the AZERO raised for liquidity
+            let set_liquidity_config_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.set_liquidity_config(
+                        MOCK_SALE_ID,
+                        LiquidityConfig {
+                            router: router_id,
+                            lp_token: lp_token_id,
+                            azero_percentage: 50,
+                            lp_unlock_timestamp: 0,
+                        },
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), set_liquidity_config_message, 0, None)
+                .await
+                .unwrap();
+
+            // when AZERO has been held back for liquidity
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+                .await
+                .unwrap();
+            // * it escrows the rest of the AZERO
+            let sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: Sale = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
+                .await
+                .return_value()
+                .unwrap();
+            assert_eq!(sale.proceeds, MOCK_IN_UNIT);
+            let liquidity_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.liquidity(MOCK_SALE_ID));
+            let liquidity: Liquidity = client
+                .call_dry_run(&ink_e2e::alice(), &liquidity_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(liquidity.azero_amount, MOCK_IN_UNIT);
+            // = when finalized
+            // = * it adds the AZERO and matching out_token to the router
+            let finalize_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.finalize(MOCK_SALE_ID));
+            client
+                .call(&ink_e2e::alice(), finalize_message, 0, None)
+                .await
+                .unwrap();
+            let balance_message =
+                build_message::<ButtonRef>(to_token_id).call(|button| button.balance_of(router_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_OUT_UNIT);
+            // = * it locks the LP tokens in the contract
+            let balance_message = build_message::<ButtonRef>(lp_token_id)
+                .call(|button| button.balance_of(token_sale_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_IN_UNIT);
+            let liquidity: Liquidity = client
+                .call_dry_run(&ink_e2e::alice(), &liquidity_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                liquidity,
+                Liquidity {
+                    azero_amount: 0,
+                    lp_amount: MOCK_IN_UNIT
+                }
+            );
+            // = * it stops purchases
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &buy_message, MOCK_IN_UNIT, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Finalized))
+            );
+            // = * it lets the admin release the escrowed AZERO
+            let original_token_sale_azero_balance: Balance =
+                client.balance(token_sale_id).await.unwrap();
+            let release_tranche_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.release_tranche(MOCK_SALE_ID, 0));
+            let result = client
+                .call(&ink_e2e::alice(), release_tranche_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(MOCK_IN_UNIT));
+            assert_eq!(
+                client.balance(token_sale_id).await.unwrap(),
+                original_token_sale_azero_balance - MOCK_IN_UNIT
+            );
+            // == when LP tokens are unlocked
+            // == * it releases the LP tokens to the admin
+            let release_lp_tokens_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.release_lp_tokens(MOCK_SALE_ID));
+            client
+                .call(&ink_e2e::alice(), release_lp_tokens_message, 0, None)
+                .await
+                .unwrap();
+            let balance_message = build_message::<ButtonRef>(lp_token_id)
+                .call(|button| button.balance_of(alice_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, MOCK_IN_UNIT);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_flashloan(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+
+            // Instantiate token with room under the cap to flash mint
+            let token_constructor = ButtonRef::new_with_initial_supply(
+                TOKEN_BALANCE,
+                TOKEN_BALANCE / 2,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+            // 1% fee
+            let set_flash_mint_config_message =
+                build_message::<ButtonRef>(token_id).call(|button| {
+                    button.set_flash_mint_config(FlashMintConfig {
+                        limit: 1_000,
+                        fee_basis_points: 100,
+                    })
+                });
+            client
+                .call(&ink_e2e::alice(), set_flash_mint_config_message, 0, None)
+                .await
+                .unwrap();
+
+            // Instantiate borrowers and give them enough to pay the fee
+            let mut borrower_ids: Vec<AccountId> = vec![];
+            for repay in [true, false] {
+                let borrower_constructor = MockFlashBorrowerRef::new(token_id, repay);
+                let borrower_id: AccountId = client
+                    .instantiate(
+                        "az_mock_flash_borrower",
+                        &ink_e2e::alice(),
+                        borrower_constructor,
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("Borrower instantiate failed")
+                    .account_id;
+                let transfer_message = build_message::<ButtonRef>(token_id)
+                    .call(|button| button.transfer(borrower_id, 10, vec![]));
+                client
+                    .call(&ink_e2e::alice(), transfer_message, 0, None)
+                    .await
+                    .unwrap();
+                borrower_ids.push(borrower_id);
+            }
+
+            // when borrower does not repay
+            // * it raises an error
+            let flashloan_message = build_message::<ButtonRef>(token_id)
+                .call(|button| button.flashloan(borrower_ids[1], token_id, 1_000, vec![]));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &flashloan_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom("Flash loan not repaid".to_string()))
+            );
+            // when amount is over the limit
+            // * it raises an error
+            let flashloan_message = build_message::<ButtonRef>(token_id)
+                .call(|button| button.flashloan(borrower_ids[0], token_id, 1_001, vec![]));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &flashloan_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(PSP22Error::Custom(
+                    "Amount exceeds flash mint limit".to_string()
+                ))
+            );
+            // when borrower repays
+            // * it burns the loan and pays the fee to the owner
+            let flashloan_message = build_message::<ButtonRef>(token_id)
+                .call(|button| button.flashloan(borrower_ids[0], token_id, 1_000, vec![]));
+            client
+                .call(&ink_e2e::bob(), flashloan_message, 0, None)
+                .await
+                .unwrap();
+            let balance_message = build_message::<ButtonRef>(token_id)
+                .call(|button| button.balance_of(borrower_ids[0]));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 0);
+            let balance_message = build_message::<ButtonRef>(token_id)
+                .call(|button| button.balance_of(alice_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, TOKEN_BALANCE / 2 - 20 + 10);
+            let total_supply_message =
+                build_message::<ButtonRef>(token_id).call(|button| button.total_supply());
+            let total_supply: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &total_supply_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(total_supply, TOKEN_BALANCE / 2);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_quote(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 20));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 20)
+                });
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // * it matches buy for every amount, until and after selling out
+            let mut bought_out_amount: Balance = 0;
+            for in_amount in [
+                0,
+                1,
+                MOCK_IN_UNIT / MOCK_OUT_UNIT - 1,
+                MOCK_IN_UNIT / MOCK_OUT_UNIT,
+                MOCK_IN_UNIT / MOCK_OUT_UNIT + 1,
+                MOCK_IN_UNIT - 1,
+                MOCK_IN_UNIT,
+                MOCK_IN_UNIT * 3 + 7,
+                MOCK_IN_UNIT * 100,
+                MOCK_IN_UNIT,
+            ] {
+                let quote_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                    .call(|token_sale| token_sale.quote(MOCK_SALE_ID, in_amount, None));
+                let quote: Result<Quote> = client
+                    .call_dry_run(&ink_e2e::bob(), &quote_message, 0, None)
+                    .await
+                    .return_value();
+                let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                    .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+                let quote: Quote = match quote {
+                    Ok(quote) => quote,
+                    Err(e) => {
+                        let result = client
+                            .call_dry_run(&ink_e2e::bob(), &buy_message, in_amount, None)
+                            .await
+                            .return_value();
+                        assert_eq!(result, Err(e));
+                        continue;
+                    }
+                };
+                let original_token_sale_azero_balance: Balance =
+                    client.balance(token_sale_id).await.unwrap();
+                let result = client
+                    .call(&ink_e2e::bob(), buy_message, in_amount, None)
+                    .await
+                    .unwrap()
+                    .return_value();
+                assert_eq!(result, Ok((quote.accepted_in, quote.out_amount)));
+                assert_eq!(quote.accepted_in + quote.refund, in_amount);
+                assert_eq!(
+                    client.balance(token_sale_id).await.unwrap(),
+                    original_token_sale_azero_balance + quote.accepted_in
+                );
+                bought_out_amount += quote.out_amount;
+                let balance_message = build_message::<ButtonRef>(to_token_id)
+                    .call(|button| button.balance_of(bob_account_id));
+                let balance: Balance = client
+                    .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                    .await
+                    .return_value();
+                assert_eq!(balance, bought_out_amount);
+                assert_eq!(
+                    quote.price_after.is_some(),
+                    bought_out_amount < MOCK_OUT_UNIT * 20
+                );
+            }
+            assert_eq!(bought_out_amount, MOCK_OUT_UNIT * 20);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_refund(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+            let charlie_account_id: AccountId = account_id(ink_e2e::charlie());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 10));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 10)
+                });
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let set_guardian_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_guardian(Some(charlie_account_id)));
+            client
+                .call(&ink_e2e::alice(), set_guardian_message, 0, None)
+                .await
+                .unwrap();
+
+            // Bob buys
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+                .await
+                .unwrap();
+
+            // when sale has not been cancelled
+            // * it raises an error
+            let refund_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.refund(MOCK_SALE_ID));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &refund_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Active))
+            );
+            // when sale has been cancelled by the guardian
+            let cancel_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.cancel(MOCK_SALE_ID));
+            client
+                .call(&ink_e2e::charlie(), cancel_message, 0, None)
+                .await
+                .unwrap();
+            // * it stops purchases
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &buy_message, MOCK_IN_UNIT, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::InvalidSaleStatus(SaleStatus::Cancelled))
+            );
+            // = when buyer has not approved the out_token they received
+            // = * it raises an error
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &refund_message, 0, None)
+                .await
+                .return_value();
+            assert!(matches!(result, Err(AZTokenSaleError::PSP22Error(_))));
+            // = when buyer has approved the out_token they received
+            // = * it takes back the out_token and returns the AZERO
+            let approve_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.approve(token_sale_id, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::bob(), approve_message, 0, None)
+                .await
+                .unwrap();
+            let original_token_sale_azero_balance: Balance =
+                client.balance(token_sale_id).await.unwrap();
+            let result = client
+                .call(&ink_e2e::bob(), refund_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(MOCK_IN_UNIT * 2));
+            assert_eq!(
+                client.balance(token_sale_id).await.unwrap(),
+                original_token_sale_azero_balance - MOCK_IN_UNIT * 2
+            );
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(bob_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 0);
+            // = * it returns the out_token to the sale's inventory
+            let sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: Sale = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
+                .await
+                .return_value()
+                .unwrap();
+            assert_eq!(sale.inventory, MOCK_OUT_UNIT * 10);
+            assert_eq!(sale.proceeds, 0);
+            // = * it lets the admin withdraw the inventory
+            let withdraw_inventory_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.withdraw_inventory(MOCK_SALE_ID));
+            let result = client
+                .call(&ink_e2e::alice(), withdraw_inventory_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(MOCK_OUT_UNIT * 10));
+            let balance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.balance_of(alice_account_id));
+            let balance: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, TOKEN_BALANCE);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_screening_oracle(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let bob_account_id: AccountId = account_id(ink_e2e::bob());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate screening oracle
+            let screening_oracle_constructor = MockScreeningOracleRef::new();
+            let screening_oracle_id: AccountId = client
+                .instantiate(
+                    "az_mock_screening_oracle",
+                    &ink_e2e::alice(),
+                    screening_oracle_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("Screening oracle instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let set_screening_oracle_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_screening_oracle(Some(screening_oracle_id)));
+            client
+                .call(&ink_e2e::alice(), set_screening_oracle_message, 0, None)
+                .await
+                .unwrap();
+
+            // when the screening oracle blocks the caller
+            // * it raises an error
+            let set_blocked_message = build_message::<MockScreeningOracleRef>(screening_oracle_id)
+                .call(|screening_oracle| screening_oracle.set_blocked(bob_account_id, true));
+            client
+                .call(&ink_e2e::alice(), set_blocked_message, 0, None)
+                .await
+                .unwrap();
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &buy_message, MOCK_IN_UNIT, None)
+                .await
+                .return_value();
+            assert_eq!(
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Account is blocked".to_string()
+                ))
+            );
+            // when the screening oracle does not block the caller
+            // * it works
+            let set_blocked_message = build_message::<MockScreeningOracleRef>(screening_oracle_id)
+                .call(|screening_oracle| screening_oracle.set_blocked(bob_account_id, false));
+            client
+                .call(&ink_e2e::alice(), set_blocked_message, 0, None)
+                .await
+                .unwrap();
+            let result = client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok((MOCK_IN_UNIT, MOCK_OUT_UNIT)));
 
-        // === HELPERS ===
-        fn account_id(k: Keypair) -> AccountId {
-            AccountId::try_from(k.public_key().to_account_id().as_ref())
-                .expect("account keyring has a valid account id")
+            Ok(())
         }
 
-        // === TEST HANDLES ===
         #[ink_e2e::test]
-        async fn test_add_amount_for_sale(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
-            // Instantiate to token
-            let to_token_constructor = ButtonRef::new(
+        async fn test_set_code(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
                 TOKEN_BALANCE,
-                Some("Button".to_string()),
-                Some("BTN".to_string()),
-                6,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
             );
             let to_token_id: AccountId = client
-                .instantiate(
-                    "az_button",
-                    &ink_e2e::alice(),
-                    to_token_constructor,
-                    0,
-                    None,
-                )
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
                 .await
-                .expect("Reward token instantiate failed")
+                .expect("Token instantiate failed")
                 .account_id;
+
             // Instantiate token sale for smart contract
-            let token_sale_constructor =
-                AZTokenSaleRef::new(to_token_id, MOCK_IN_UNIT, MOCK_OUT_UNIT);
+            let token_sale_constructor = AZTokenSaleRef::new();
             let token_sale_id: AccountId = client
                 .instantiate(
                     "az_token_sale",
@@ -275,60 +5662,112 @@ mod az_token_sale {
                 .await
                 .expect("AZ Token Sale instantiate failed")
                 .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
 
+            // Stock the token sale
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
             let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
-                .call(|token_sale| token_sale.add_amount_for_sale(TOKEN_BALANCE));
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 2));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let original_token_sale_azero_balance: Balance =
+                client.balance(token_sale_id).await.unwrap();
+
+            // Upload v2
+            let code_hash: Hash = client
+                .upload("az_token_sale_v2", &ink_e2e::alice(), None)
+                .await
+                .expect("AZ Token Sale v2 upload failed")
+                .code_hash;
+
             // when called by non-admin
             // * it raises an error
+            let set_code_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_code(code_hash));
             let result = client
-                .call_dry_run(&ink_e2e::charlie(), &add_amount_for_sale_message, 0, None)
+                .call_dry_run(&ink_e2e::bob(), &set_code_message, 0, None)
                 .await
                 .return_value();
             assert_eq!(result, Err(AZTokenSaleError::Unauthorised));
             // when called by admin
-            // = when amount added in is not divisible by out_unit
-            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
-                .call(|token_sale| token_sale.add_amount_for_sale(1));
-            // # it raises an error
+            // * it upgrades the code
+            client
+                .call(&ink_e2e::alice(), set_code_message, 0, None)
+                .await
+                .unwrap();
+            // = when the next release's migrate is called
+            // = * it migrates storage to the next version
+            let migrate_message = build_message::<AZTokenSaleV2Ref>(token_sale_id)
+                .call(|token_sale| token_sale.migrate());
             let result = client
-                .call_dry_run(&ink_e2e::alice(), &add_amount_for_sale_message, 0, None)
+                .call(&ink_e2e::alice(), migrate_message, 0, None)
                 .await
+                .unwrap()
                 .return_value();
-            assert_eq!(
-                result,
-                Err(AZTokenSaleError::UnprocessableEntity(
-                    "Amount must be in multiples of out_unit".to_string()
-                ))
-            );
-            // = when amount added in is divisible by out_unit
-            // = * it transfers the token from admin to itself
-            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
-                .call(|to_token| to_token.increase_allowance(token_sale_id, u128::MAX));
-            client
-                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+            assert_eq!(result, Ok(STORAGE_VERSION + 1));
+            // = * it keeps the config
+            let config_message = build_message::<AZTokenSaleV2Ref>(token_sale_id)
+                .call(|token_sale| token_sale.config());
+            let config: ConfigV2 = client
+                .call_dry_run(&ink_e2e::alice(), &config_message, 0, None)
                 .await
-                .unwrap();
-            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
-                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_OUT_UNIT));
-            client
-                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .return_value();
+            assert_eq!(config.version, STORAGE_VERSION + 1);
+            assert_eq!(config.admin, alice_account_id);
+            // = * it keeps the sales
+            let sale_message = build_message::<AZTokenSaleV2Ref>(token_sale_id)
+                .call(|token_sale| token_sale.sale(MOCK_SALE_ID));
+            let sale: SaleV2 = client
+                .call_dry_run(&ink_e2e::alice(), &sale_message, 0, None)
                 .await
+                .return_value()
                 .unwrap();
+            assert_eq!(sale.admin, alice_account_id);
+            assert_eq!(sale.out_token, to_token_id);
+            assert_eq!(sale.in_unit, MOCK_IN_UNIT);
+            assert_eq!(sale.out_unit, MOCK_OUT_UNIT);
+            assert_eq!(sale.inventory, MOCK_OUT_UNIT * 2);
+            // = * it keeps the out token balance
             let balance_message = build_message::<ButtonRef>(to_token_id)
                 .call(|button| button.balance_of(token_sale_id));
             let balance: Balance = client
                 .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
                 .await
                 .return_value();
-            assert_eq!(MOCK_OUT_UNIT, balance);
+            assert_eq!(balance, MOCK_OUT_UNIT * 2);
+            // = * it keeps the AZERO balance
+            assert_eq!(
+                client.balance(token_sale_id).await.unwrap(),
+                original_token_sale_azero_balance
+            );
 
             Ok(())
         }
 
         #[ink_e2e::test]
-        async fn test_buy(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+        async fn test_usd_price(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
             let alice_account_id: AccountId = account_id(ink_e2e::alice());
-            let bob_account_id: AccountId = account_id(ink_e2e::bob());
 
             // Instantiate token
             let token_constructor = ButtonRef::new(
@@ -343,9 +5782,22 @@ mod az_token_sale {
                 .expect("Token instantiate failed")
                 .account_id;
 
+            // Instantiate price oracle with AZERO at 0.25 USD, last updated long ago
+            let price_oracle_constructor = MockPriceOracleRef::new(250_000, 0);
+            let price_oracle_id: AccountId = client
+                .instantiate(
+                    "az_mock_price_oracle",
+                    &ink_e2e::alice(),
+                    price_oracle_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("Price oracle instantiate failed")
+                .account_id;
+
             // Instantiate token sale for smart contract
-            let token_sale_constructor =
-                AZTokenSaleRef::new(to_token_id, MOCK_IN_UNIT, MOCK_OUT_UNIT);
+            let token_sale_constructor = AZTokenSaleRef::new();
             let token_sale_id: AccountId = client
                 .instantiate(
                     "az_token_sale",
@@ -357,109 +5809,276 @@ mod az_token_sale {
                 .await
                 .expect("AZ Token Sale instantiate failed")
                 .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT));
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+            let set_price_oracle_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.set_price_oracle(Some(price_oracle_id), 60_000));
+            client
+                .call(&ink_e2e::alice(), set_price_oracle_message, 0, None)
+                .await
+                .unwrap();
+            // 1 USD buys 2 out_token
+            let set_usd_price_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.set_usd_price(
+                        MOCK_SALE_ID,
+                        Some(UsdPrice {
+                            usd_amount: 1_000_000,
+                            out_amount: 2,
+                        }),
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), set_usd_price_message, 0, None)
+                .await
+                .unwrap();
 
-            // when in amount is zero
+            // when the oracle price is older than the max price age
             // * it raises an error
-            let buy_message =
-                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| token_sale.buy());
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
             let result = client
-                .call_dry_run(&ink_e2e::alice(), &buy_message, 0, None)
+                .call_dry_run(&ink_e2e::bob(), &buy_message, AZERO_UNIT * 2, None)
                 .await
                 .return_value();
             assert_eq!(
                 result,
                 Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string()
+                    "Oracle price is stale".to_string()
                 ))
             );
+            // when the oracle price is fresh enough
+            // * it prices out_token in USD at the oracle's price
+            let set_price_oracle_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.set_price_oracle(Some(price_oracle_id), Timestamp::MAX)
+                });
+            client
+                .call(&ink_e2e::alice(), set_price_oracle_message, 0, None)
+                .await
+                .unwrap();
+            let result = client
+                .call(&ink_e2e::bob(), buy_message, AZERO_UNIT * 2, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok((AZERO_UNIT * 2, 1)));
 
-            // when in amount is positive
-            // = when amount is not a multiple of in_unit
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn test_vote_tranche(mut client: ::ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice_account_id: AccountId = account_id(ink_e2e::alice());
+            let charlie_account_id: AccountId = account_id(ink_e2e::charlie());
+
+            // Instantiate token
+            let token_constructor = ButtonRef::new(
+                TOKEN_BALANCE,
+                Some("DIBS".to_string()),
+                Some("DIBS".to_string()),
+                12,
+            );
+            let to_token_id: AccountId = client
+                .instantiate("az_button", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("Token instantiate failed")
+                .account_id;
+
+            // Instantiate token sale for smart contract
+            let token_sale_constructor = AZTokenSaleRef::new();
+            let token_sale_id: AccountId = client
+                .instantiate(
+                    "az_token_sale",
+                    &ink_e2e::alice(),
+                    token_sale_constructor,
+                    0,
+                    None,
+                )
+                .await
+                .expect("AZ Token Sale instantiate failed")
+                .account_id;
+            let create_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.create_sale(
+                        alice_account_id,
+                        to_token_id,
+                        MOCK_IN_UNIT,
+                        MOCK_OUT_UNIT,
+                        0,
+                        None,
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), create_sale_message, 0, None)
+                .await
+                .unwrap();
+            let increase_allowance_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.increase_allowance(token_sale_id, MOCK_OUT_UNIT * 10));
+            client
+                .call(&ink_e2e::alice(), increase_allowance_message, 0, None)
+                .await
+                .unwrap();
+            let add_amount_for_sale_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.add_amount_for_sale(MOCK_SALE_ID, MOCK_OUT_UNIT * 10)
+                });
+            client
+                .call(&ink_e2e::alice(), add_amount_for_sale_message, 0, None)
+                .await
+                .unwrap();
+
+            // Release half of the proceeds straight away and half by vote
+            let set_tranches_message =
+                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| {
+                    token_sale.set_tranches(
+                        MOCK_SALE_ID,
+                        vec![
+                            Tranche {
+                                percentage: 50,
+                                unlock: TrancheUnlock::Timestamp(0),
+                            },
+                            Tranche {
+                                percentage: 50,
+                                unlock: TrancheUnlock::Vote,
+                            },
+                        ],
+                    )
+                });
+            client
+                .call(&ink_e2e::alice(), set_tranches_message, 0, None)
+                .await
+                .unwrap();
+
+            // Bob buys twice as much as Charlie
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            client
+                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+                .await
+                .unwrap();
+            let buy_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.buy(MOCK_SALE_ID, None));
+            client
+                .call(&ink_e2e::charlie(), buy_message, MOCK_IN_UNIT, None)
+                .await
+                .unwrap();
+            let finalize_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.finalize(MOCK_SALE_ID));
+            client
+                .call(&ink_e2e::alice(), finalize_message, 0, None)
+                .await
+                .unwrap();
+
+            // when buyers holding no more than half of the out_token sold have voted
+            // * it keeps the tranche locked
+            let vote_tranche_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.vote_tranche(MOCK_SALE_ID, 1));
+            let result = client
+                .call(&ink_e2e::charlie(), vote_tranche_message, 0, None)
+                .await
+                .unwrap()
+                .return_value();
+            assert_eq!(result, Ok(MOCK_OUT_UNIT));
+            let release_tranche_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.release_tranche(MOCK_SALE_ID, 1));
             let result = client
-                .call_dry_run(&ink_e2e::alice(), &buy_message, MOCK_IN_UNIT + 1, None)
+                .call_dry_run(&ink_e2e::alice(), &release_tranche_message, 0, None)
                 .await
                 .return_value();
             assert_eq!(
                 result,
                 Err(AZTokenSaleError::UnprocessableEntity(
-                    "In amount must be in multiples of in_unit".to_string()
+                    "Tranche is locked".to_string()
                 ))
             );
-            // = when in amount is a multiple of in_unit
-            // == when there is enough stock to fill full order
+            // = when a voter no longer holds the out_token they bought
+            // = * it only counts what they still hold
             let transfer_message = build_message::<ButtonRef>(to_token_id)
-                .call(|button| button.transfer(token_sale_id, MOCK_OUT_UNIT * 2, vec![]));
-            let transfer_result = client
-                .call(&ink_e2e::alice(), transfer_message, 0, None)
+                .call(|button| button.transfer(charlie_account_id, MOCK_OUT_UNIT, vec![]));
+            client
+                .call(&ink_e2e::bob(), transfer_message, 0, None)
                 .await
-                .unwrap()
-                .dry_run
-                .exec_result
-                .result;
-            assert!(transfer_result.is_ok());
-
-            // == * it works
-            let original_alice_azero_balance: Balance =
-                client.balance(alice_account_id).await.unwrap();
-            let buy_message =
-                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| token_sale.buy());
-            let buy_result = client
-                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT, None)
+                .unwrap();
+            let vote_tranche_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.vote_tranche(MOCK_SALE_ID, 1));
+            let result = client
+                .call_dry_run(&ink_e2e::bob(), &vote_tranche_message, 0, None)
                 .await
-                .unwrap()
-                .dry_run
-                .exec_result
-                .result;
-            assert!(buy_result.is_ok());
-
-            // == * it transfers the out amount to the caller
-            let balance_message = build_message::<ButtonRef>(to_token_id)
-                .call(|button| button.balance_of(bob_account_id));
+                .return_value();
+            assert_eq!(result, Ok(MOCK_OUT_UNIT));
+            // when buyers holding more than half of the out_token sold have voted
+            // * it unlocks the tranche
+            let transfer_message = build_message::<ButtonRef>(to_token_id)
+                .call(|button| button.transfer(account_id(ink_e2e::bob()), MOCK_OUT_UNIT, vec![]));
+            client
+                .call(&ink_e2e::charlie(), transfer_message, 0, None)
+                .await
+                .unwrap();
             let result = client
-                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .call(&ink_e2e::bob(), vote_tranche_message, 0, None)
                 .await
+                .unwrap()
                 .return_value();
-            assert_eq!(result, MOCK_OUT_UNIT);
-            // == * it transfers the in amount to the admin
-            assert_eq!(
-                client.balance(alice_account_id).await.unwrap(),
-                original_alice_azero_balance + MOCK_IN_UNIT
-            );
-
-            // == when there is only enough stock to partially fill order
-            // == * it works
-            let original_token_sale_azero_balance: Balance =
-                client.balance(token_sale_id).await.unwrap();
-            let buy_message =
-                build_message::<AZTokenSaleRef>(token_sale_id).call(|token_sale| token_sale.buy());
-            let buy_result = client
-                .call(&ink_e2e::bob(), buy_message, MOCK_IN_UNIT * 2, None)
+            assert_eq!(result, Ok(MOCK_OUT_UNIT * 2));
+            let tranche_votes_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.tranche_votes(MOCK_SALE_ID, 1));
+            let votes: Balance = client
+                .call_dry_run(&ink_e2e::alice(), &tranche_votes_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(votes, MOCK_OUT_UNIT * 3);
+            let result = client
+                .call(&ink_e2e::alice(), release_tranche_message, 0, None)
                 .await
                 .unwrap()
-                .dry_run
-                .exec_result
-                .result;
-            assert!(buy_result.is_ok());
-
-            // == * it transfers the available out amount to the caller
-            let balance_message = build_message::<ButtonRef>(to_token_id)
-                .call(|button| button.balance_of(bob_account_id));
+                .return_value();
+            assert_eq!(result, Ok(MOCK_IN_UNIT * 3 / 2));
+            let locked_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.locked(MOCK_SALE_ID));
             let result = client
-                .call_dry_run(&ink_e2e::alice(), &balance_message, 0, None)
+                .call_dry_run(&ink_e2e::alice(), &locked_message, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Ok(MOCK_IN_UNIT * 3 / 2));
+            // = when voting again
+            // = * it raises an error
+            let vote_tranche_message = build_message::<AZTokenSaleRef>(token_sale_id)
+                .call(|token_sale| token_sale.vote_tranche(MOCK_SALE_ID, 1));
+            let result = client
+                .call_dry_run(&ink_e2e::charlie(), &vote_tranche_message, 0, None)
                 .await
                 .return_value();
-            assert_eq!(result, MOCK_OUT_UNIT * 2);
-
-            // == * it transfers the applicable in amount to the admin
-            assert_eq!(
-                client.balance(alice_account_id).await.unwrap(),
-                original_alice_azero_balance + MOCK_IN_UNIT * 2
-            );
-
-            // == * it refunds the unused in amount to the buyer
             assert_eq!(
-                client.balance(token_sale_id).await.unwrap(),
-                original_token_sale_azero_balance
+                result,
+                Err(AZTokenSaleError::UnprocessableEntity(
+                    "Tranche already released".to_string()
+                ))
             );
 
             Ok(())
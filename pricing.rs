@@ -0,0 +1,464 @@
+use crate::az_token_sale::{BonusBracket, Sale};
+use openbrush::traits::Balance;
+use primitive_types::U512;
+
+// Smallest units in one AZERO
+pub const AZERO_UNIT: Balance = 1_000_000_000_000;
+
+// Overflow-safe pricing for a sale. Everything is worked out in U512, which
+// holds the product of any three Balances, and rounds in the contract's
+// favour, so a buyer never receives more out_token than they paid for at the
+// sale price and bonus.
+#[derive(Debug, Clone, Copy)]
+pub struct Price<'a> {
+    pub in_unit: Balance,
+    pub out_unit: Balance,
+    // Ordered by min_in_amount, ascending, with non-decreasing bonus_percentage
+    pub bonus_brackets: &'a [BonusBracket],
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fill {
+    // AZERO kept by the contract
+    pub accepted_in: Balance,
+    pub out_amount: Balance,
+    // AZERO returned to the buyer, including dust too small to buy any out_token
+    pub refund: Balance,
+}
+
+impl<'a> Price<'a> {
+    pub fn new(sale: &Sale, bonus_brackets: &'a [BonusBracket]) -> Self {
+        Self {
+            in_unit: sale.in_unit,
+            out_unit: sale.out_unit,
+            bonus_brackets,
+        }
+    }
+
+    // Prices usd_amount worth of out_amount out_token, given the USD value of
+    // one AZERO in the same units. None when the price does not fit in a
+    // Balance even in lowest terms.
+    pub fn from_usd(
+        usd_amount: Balance,
+        out_amount: Balance,
+        azero_usd_price: Balance,
+        bonus_brackets: &'a [BonusBracket],
+    ) -> Option<Self> {
+        let mut in_unit: U512 = U512::from(usd_amount) * U512::from(AZERO_UNIT);
+        let mut out_unit: U512 = U512::from(out_amount) * U512::from(azero_usd_price);
+        if in_unit.is_zero() || out_unit.is_zero() {
+            return None;
+        }
+        let (mut a, mut b) = (in_unit, out_unit);
+        while !b.is_zero() {
+            (a, b) = (b, a % b);
+        }
+        in_unit /= a;
+        out_unit /= a;
+        if in_unit > U512::from(Balance::MAX) || out_unit > U512::from(Balance::MAX) {
+            return None;
+        }
+
+        Some(Self {
+            in_unit: in_unit.as_u128(),
+            out_unit: out_unit.as_u128(),
+            bonus_brackets,
+        })
+    }
+
+    pub fn bonus_percentage(&self, in_amount: Balance) -> u8 {
+        self.bonus_brackets
+            .iter()
+            .rev()
+            .find(|bracket| in_amount >= bracket.min_in_amount)
+            .map_or(0, |bracket| bracket.bonus_percentage)
+    }
+
+    // Least in amount that buys any out_token.
+    pub fn min_in_amount(&self) -> Balance {
+        Self::saturate(
+            (U512::from(self.in_unit) + U512::from(self.out_unit) - U512::from(1))
+                / U512::from(self.out_unit),
+        )
+    }
+
+    // out_token bought by in_amount before any bonus, rounded down.
+    pub fn base_out_amount(&self, in_amount: Balance) -> Balance {
+        Self::saturate(U512::from(in_amount) * U512::from(self.out_unit) / U512::from(self.in_unit))
+    }
+
+    // out_token bought by in_amount including its bracket's bonus, rounded down.
+    pub fn out_amount(&self, in_amount: Balance) -> Balance {
+        let base_out_amount: Balance = self.base_out_amount(in_amount);
+        let bonus_out_amount: Balance = Self::saturate(
+            U512::from(base_out_amount) * U512::from(self.bonus_percentage(in_amount))
+                / U512::from(100),
+        );
+
+        base_out_amount.saturating_add(bonus_out_amount)
+    }
+
+    // Largest in amount, up to in_amount, whose out amount including bonus
+    // fits in available_out_amount.
+    pub fn max_in_amount(&self, in_amount: Balance, available_out_amount: Balance) -> Balance {
+        // Out amount only grows with in amount, so walk the brackets down from
+        // the one in_amount falls in and stop at the first that fits something.
+        let mut upper_in_amount: Balance = in_amount;
+        let brackets = self
+            .bonus_brackets
+            .iter()
+            .rev()
+            .map(|bracket| (bracket.min_in_amount, bracket.bonus_percentage))
+            .chain(core::iter::once((0, 0)));
+        for (min_in_amount, bonus_percentage) in brackets {
+            if min_in_amount > upper_in_amount {
+                continue;
+            }
+            let fitting_in_amount: Balance =
+                (U512::from(available_out_amount) * U512::from(self.in_unit) * U512::from(100)
+                    / (U512::from(self.out_unit) * U512::from(100 + u16::from(bonus_percentage))))
+                .min(U512::from(upper_in_amount))
+                .as_u128();
+            if fitting_in_amount >= min_in_amount {
+                return fitting_in_amount;
+            }
+            upper_in_amount = min_in_amount - 1;
+        }
+
+        0
+    }
+
+    // Splits in_amount into what buys out_token, up to available_out_amount,
+    // and what goes back to the buyer. The accepted amount is the least that
+    // buys the same base out amount in the same bracket, so the refund
+    // includes any dust but never costs the buyer their bonus.
+    pub fn fill(&self, in_amount: Balance, available_out_amount: Balance) -> Fill {
+        let max_in_amount: Balance = self.max_in_amount(in_amount, available_out_amount);
+        let base_out_amount: Balance = self.base_out_amount(max_in_amount);
+        let bracket_min_in_amount: Balance = self
+            .bonus_brackets
+            .iter()
+            .rev()
+            .map(|bracket| bracket.min_in_amount)
+            .find(|min_in_amount| max_in_amount >= *min_in_amount)
+            .unwrap_or(0);
+        let accepted_in: Balance = Self::saturate(
+            (U512::from(base_out_amount) * U512::from(self.in_unit) + U512::from(self.out_unit)
+                - U512::from(1))
+                / U512::from(self.out_unit),
+        )
+        .max(bracket_min_in_amount)
+        .min(max_in_amount);
+        let out_amount: Balance = self.out_amount(accepted_in);
+        // Holds as long as bonus percentages never decrease with in amount
+        assert!(out_amount <= available_out_amount);
+
+        Fill {
+            accepted_in,
+            out_amount,
+            refund: in_amount - accepted_in,
+        }
+    }
+
+    fn saturate(value: U512) -> Balance {
+        if value > U512::from(Balance::MAX) {
+            Balance::MAX
+        } else {
+            value.as_u128()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::prelude::vec::Vec;
+    use proptest::prelude::*;
+
+    // === CONSTANTS ===
+    const MOCK_IN_UNIT: Balance = 250;
+    const MOCK_OUT_UNIT: Balance = 1;
+
+    // === HELPERS ===
+    fn mock_bonus_brackets() -> Vec<BonusBracket> {
+        vec![
+            BonusBracket {
+                min_in_amount: MOCK_IN_UNIT * 100,
+                bonus_percentage: 5,
+            },
+            BonusBracket {
+                min_in_amount: MOCK_IN_UNIT * 1_000,
+                bonus_percentage: 10,
+            },
+        ]
+    }
+
+    fn mock_price(bonus_brackets: &[BonusBracket]) -> Price<'_> {
+        Price {
+            in_unit: MOCK_IN_UNIT,
+            out_unit: MOCK_OUT_UNIT,
+            bonus_brackets,
+        }
+    }
+
+    fn bonus_brackets_strategy() -> impl Strategy<Value = Vec<BonusBracket>> {
+        prop::collection::btree_map(1..Balance::MAX, 0..=100u8, 0..4).prop_map(|brackets| {
+            let mut bonus_percentages: Vec<u8> = brackets.values().copied().collect();
+            bonus_percentages.sort_unstable();
+            brackets
+                .into_keys()
+                .zip(bonus_percentages)
+                .map(|(min_in_amount, bonus_percentage)| BonusBracket {
+                    min_in_amount,
+                    bonus_percentage,
+                })
+                .collect()
+        })
+    }
+
+    // === TESTS ===
+    #[test]
+    fn test_out_amount() {
+        let bonus_brackets = mock_bonus_brackets();
+        let price = mock_price(&bonus_brackets);
+        // when in amount is below every bracket
+        // * it returns the base out amount
+        assert_eq!(price.out_amount(MOCK_IN_UNIT * 99), MOCK_OUT_UNIT * 99);
+        // when in amount is in a bracket
+        // * it adds that bracket's bonus
+        assert_eq!(price.out_amount(MOCK_IN_UNIT * 100), MOCK_OUT_UNIT * 105);
+        assert_eq!(
+            price.out_amount(MOCK_IN_UNIT * 1_000),
+            MOCK_OUT_UNIT * 1_100
+        );
+        // when in amount is not a multiple of in_unit
+        // * it rounds down
+        assert_eq!(price.out_amount(MOCK_IN_UNIT * 2 - 1), MOCK_OUT_UNIT);
+        // when the out amount does not fit in a Balance
+        // * it saturates instead of overflowing
+        let price = Price {
+            in_unit: 1,
+            out_unit: 2,
+            bonus_brackets: &bonus_brackets,
+        };
+        assert_eq!(price.out_amount(Balance::MAX), Balance::MAX);
+    }
+
+    #[test]
+    fn test_from_usd() {
+        // when AZERO is worth 0.25 USD and 2 out_token cost 1 USD, in 6 decimals
+        // * it sells 1 out_token for 2 AZERO
+        let price = Price::from_usd(1_000_000, 2, 250_000, &[]).unwrap();
+        assert_eq!(price.base_out_amount(AZERO_UNIT * 2), 1);
+        assert_eq!(price.min_in_amount(), AZERO_UNIT * 2);
+        // * it keeps the units in lowest terms
+        assert_eq!((price.in_unit, price.out_unit), (AZERO_UNIT * 2, 1));
+        // when any amount is zero
+        // * it returns None
+        assert!(Price::from_usd(0, 2, 250_000, &[]).is_none());
+        assert!(Price::from_usd(1_000_000, 0, 250_000, &[]).is_none());
+        assert!(Price::from_usd(1_000_000, 2, 0, &[]).is_none());
+        // when the units do not fit in a Balance
+        // * it returns None
+        assert!(Price::from_usd(Balance::MAX, 1, 1, &[]).is_none());
+    }
+
+    #[test]
+    fn test_min_in_amount() {
+        // when in_unit is a multiple of out_unit
+        // * it returns the AZERO for one out_token
+        let price = mock_price(&[]);
+        assert_eq!(price.min_in_amount(), MOCK_IN_UNIT / MOCK_OUT_UNIT);
+        // when in_unit is not a multiple of out_unit
+        // * it rounds up
+        let price = Price {
+            in_unit: 7,
+            out_unit: 3,
+            bonus_brackets: &[],
+        };
+        assert_eq!(price.min_in_amount(), 3);
+        // when out_unit is larger than in_unit
+        // * it returns one
+        let price = Price {
+            in_unit: 3,
+            out_unit: 7,
+            bonus_brackets: &[],
+        };
+        assert_eq!(price.min_in_amount(), 1);
+    }
+
+    #[test]
+    fn test_max_in_amount() {
+        let bonus_brackets = mock_bonus_brackets();
+        let price = mock_price(&bonus_brackets);
+        // when stock covers the order including bonus
+        // * it returns the in amount
+        assert_eq!(
+            price.max_in_amount(MOCK_IN_UNIT * 100, MOCK_OUT_UNIT * 105),
+            MOCK_IN_UNIT * 100
+        );
+        // when stock covers the base amount but not the bonus
+        // = when the reduced order stays in the same bracket
+        // = * it fills as much as fits with that bracket's bonus
+        let max_in_amount: Balance =
+            price.max_in_amount(MOCK_IN_UNIT * 2_000, MOCK_OUT_UNIT * 1_210);
+        assert_eq!(max_in_amount, MOCK_IN_UNIT * 1_100);
+        assert_eq!(price.out_amount(max_in_amount), MOCK_OUT_UNIT * 1_210);
+        // = when the reduced order drops into a lower bracket
+        // = * it fills as much as fits with the lower bracket's bonus
+        let max_in_amount: Balance =
+            price.max_in_amount(MOCK_IN_UNIT * 1_000, MOCK_OUT_UNIT * 1_050);
+        assert_eq!(max_in_amount, MOCK_IN_UNIT * 1_000 - 1);
+        assert!(price.out_amount(max_in_amount) <= MOCK_OUT_UNIT * 1_050);
+        // = when the reduced order drops below every bracket
+        // = * it fills as much as fits without a bonus
+        let max_in_amount: Balance = price.max_in_amount(MOCK_IN_UNIT * 1_000, MOCK_OUT_UNIT * 50);
+        assert_eq!(max_in_amount, MOCK_IN_UNIT * 50);
+        assert_eq!(price.out_amount(max_in_amount), MOCK_OUT_UNIT * 50);
+    }
+
+    #[test]
+    fn test_fill() {
+        let bonus_brackets = mock_bonus_brackets();
+        let price = mock_price(&bonus_brackets);
+        // when in amount is a multiple of in_unit
+        // * it accepts all of it
+        assert_eq!(
+            price.fill(MOCK_IN_UNIT * 2, Balance::MAX),
+            Fill {
+                accepted_in: MOCK_IN_UNIT * 2,
+                out_amount: MOCK_OUT_UNIT * 2,
+                refund: 0
+            }
+        );
+        // when in amount is not a multiple of in_unit
+        // * it refunds the dust
+        assert_eq!(
+            price.fill(MOCK_IN_UNIT * 2 + 1, Balance::MAX),
+            Fill {
+                accepted_in: MOCK_IN_UNIT * 2,
+                out_amount: MOCK_OUT_UNIT * 2,
+                refund: 1
+            }
+        );
+        // when in amount is too small to buy any out_token
+        // * it refunds all of it
+        assert_eq!(
+            price.fill(MOCK_IN_UNIT - 1, Balance::MAX),
+            Fill {
+                accepted_in: 0,
+                out_amount: 0,
+                refund: MOCK_IN_UNIT - 1
+            }
+        );
+        // when stock runs short
+        // * it refunds what stock can not cover
+        assert_eq!(
+            price.fill(MOCK_IN_UNIT * 5, MOCK_OUT_UNIT * 3),
+            Fill {
+                accepted_in: MOCK_IN_UNIT * 3,
+                out_amount: MOCK_OUT_UNIT * 3,
+                refund: MOCK_IN_UNIT * 2
+            }
+        );
+        // when in amount is the least of a bracket but not a multiple of in_unit
+        // * it accepts all of it and keeps the bonus
+        let bonus_brackets = [BonusBracket {
+            min_in_amount: 100,
+            bonus_percentage: 5,
+        }];
+        let price = Price {
+            in_unit: 3,
+            out_unit: 1,
+            bonus_brackets: &bonus_brackets,
+        };
+        assert_eq!(
+            price.fill(100, Balance::MAX),
+            Fill {
+                accepted_in: 100,
+                out_amount: 34,
+                refund: 0
+            }
+        );
+        // when in amount is above the least of a bracket
+        // * it refunds the dust
+        assert_eq!(
+            price.fill(101, Balance::MAX),
+            Fill {
+                accepted_in: 100,
+                out_amount: 34,
+                refund: 1
+            }
+        );
+        // when out_unit is larger than in_unit
+        // * it accepts the least in amount that buys the same out amount
+        let price = Price {
+            in_unit: 3,
+            out_unit: 7,
+            bonus_brackets: &[],
+        };
+        assert_eq!(
+            price.fill(5, Balance::MAX),
+            Fill {
+                accepted_in: 5,
+                out_amount: 11,
+                refund: 0
+            }
+        );
+    }
+
+    proptest! {
+        // * it neither creates nor loses AZERO
+        // * it never sends more out_token than is available
+        // * it never sells out_token below the sale price and bonus
+        #[test]
+        fn test_fill_conserves_value(
+            in_unit in 1..=Balance::MAX,
+            out_unit in 1..=Balance::MAX,
+            bonus_brackets in bonus_brackets_strategy(),
+            in_amount in any::<Balance>(),
+            available_out_amount in any::<Balance>(),
+        ) {
+            let price = Price { in_unit, out_unit, bonus_brackets: &bonus_brackets };
+            let fill: Fill = price.fill(in_amount, available_out_amount);
+            prop_assert_eq!(fill.accepted_in + fill.refund, in_amount);
+            prop_assert!(fill.out_amount <= available_out_amount);
+            let bonus_percentage: u8 = price.bonus_percentage(fill.accepted_in);
+            prop_assert!(
+                U512::from(fill.out_amount) * U512::from(in_unit) * U512::from(100)
+                    <= U512::from(fill.accepted_in)
+                        * U512::from(out_unit)
+                        * U512::from(100 + u16::from(bonus_percentage))
+            );
+        }
+
+        // * it only refunds what could not buy more out_token
+        #[test]
+        fn test_fill_refunds_only_dust(
+            in_unit in 1..=u64::MAX as Balance,
+            out_unit in 1..=u64::MAX as Balance,
+            in_amount in 0..=u64::MAX as Balance,
+        ) {
+            let price = Price { in_unit, out_unit, bonus_brackets: &[] };
+            let fill: Fill = price.fill(in_amount, Balance::MAX);
+            prop_assert_eq!(fill.out_amount, price.base_out_amount(in_amount));
+            prop_assert_eq!(fill.out_amount > 0, in_amount >= price.min_in_amount());
+            if fill.accepted_in > 0 {
+                prop_assert!(price.base_out_amount(fill.accepted_in - 1) < fill.out_amount);
+            }
+        }
+
+        // * it gives as much out_token as paying in_amount would when in stock
+        #[test]
+        fn test_fill_keeps_bonus(
+            in_unit in 1..=u64::MAX as Balance,
+            out_unit in 1..=u64::MAX as Balance,
+            bonus_brackets in bonus_brackets_strategy(),
+            in_amount in 0..=u64::MAX as Balance,
+        ) {
+            let price = Price { in_unit, out_unit, bonus_brackets: &bonus_brackets };
+            let fill: Fill = price.fill(in_amount, Balance::MAX);
+            prop_assert_eq!(fill.out_amount, price.out_amount(in_amount));
+        }
+    }
+}
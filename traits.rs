@@ -0,0 +1,81 @@
+use openbrush::{
+    contracts::psp22::PSP22Error,
+    traits::{AccountId, Balance, Timestamp},
+};
+
+// === PRICE ORACLE ===
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct PriceData {
+    // USD value of one AZERO, in units of the oracle's choosing
+    pub price: Balance,
+    // When the price was last updated
+    pub timestamp: Timestamp,
+}
+
+#[openbrush::wrapper]
+pub type PriceOracleRef = dyn PriceOracle;
+
+// Feed of the AZERO/USD price.
+#[openbrush::trait_definition]
+pub trait PriceOracle {
+    #[ink(message)]
+    fn azero_usd_price(&self) -> PriceData;
+}
+
+// === ROUTER ===
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum RouterError {
+    Expired,
+    InsufficientAmount,
+    PSP22Error(PSP22Error),
+}
+
+#[openbrush::wrapper]
+pub type RouterRef = dyn Router;
+
+// DEX router that pairs a PSP22 token with AZERO.
+#[openbrush::trait_definition]
+pub trait Router {
+    // Returns (token amount, AZERO amount, LP token amount minted to `to`).
+    #[ink(message, payable)]
+    fn add_liquidity_native(
+        &mut self,
+        token: AccountId,
+        amount_token_desired: Balance,
+        amount_token_min: Balance,
+        amount_native_min: Balance,
+        to: AccountId,
+        deadline: Timestamp,
+    ) -> Result<(Balance, Balance, Balance), RouterError>;
+}
+
+// === PERMIT ===
+#[openbrush::wrapper]
+pub type PermitRef = dyn Permit;
+
+// Signed approvals on PSP22 tokens such as Button.
+#[openbrush::trait_definition]
+pub trait Permit {
+    #[ink(message)]
+    fn permit(
+        &mut self,
+        owner: AccountId,
+        spender: AccountId,
+        value: Balance,
+        deadline: Timestamp,
+        signature: [u8; 65],
+    ) -> Result<(), PSP22Error>;
+}
+
+// === SCREENING ORACLE ===
+#[openbrush::wrapper]
+pub type ScreeningOracleRef = dyn ScreeningOracle;
+
+// Sanctions screening service that the sale asks about each buyer.
+#[openbrush::trait_definition]
+pub trait ScreeningOracle {
+    #[ink(message)]
+    fn is_blocked(&self, account: AccountId) -> bool;
+}